@@ -12,9 +12,36 @@ pub enum TinkarosError {
     #[error("could not request/parse external config: {0}")]
     DataInvalid(String),
 
+    #[error("invalid modpack manifest:\n{}", .0.iter().map(|problem| problem.to_string()).collect::<Vec<_>>().join("\n"))]
+    InvalidManifest(Vec<ManifestProblem>),
+
+    #[error("refusing untrusted manifest: {0}")]
+    UntrustedManifest(String),
+
+    #[error("{0} does not match the hash listed in the manifest")]
+    FileHashMismatch(String),
+
+    #[error("{0} is corrupted, its download doesn't match the hash from its source")]
+    ModHashMismatch(String),
+
+    #[error("this modpack requires tinkaros {required} or newer (latest release: {}), please update tinkaros", latest.as_deref().unwrap_or("unknown"))]
+    TinkarosOutdated { required: String, latest: Option<String> },
+
     #[error("config file contents could not be parsed")]
     ConfigInvalid,
 
+    #[error("invalid manifest source: {0} (expected an https:// url or a file:// path)")]
+    ManifestSource(String),
+
+    #[error("no pack named {0} in the library")]
+    UnknownPack(String),
+
+    #[error("the manifest has no {0} channel")]
+    UnknownChannel(String),
+
+    #[error("invalid pack id: {0} (only letters, numbers, - and _ are allowed)")]
+    InvalidPackId(String),
+
     #[error("config file not found")]
     ConfigNotFound,
 
@@ -40,6 +67,21 @@ pub enum TinkarosError {
     Unknown(#[from] Box<dyn std::error::Error>)
 }
 
+#[derive(Debug)]
+pub struct ManifestProblem {
+    pub mod_name: Option<String>,
+    pub problem: String
+}
+
+impl std::fmt::Display for ManifestProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.mod_name {
+            Some(name) => write!(f, "{name}: {}", self.problem),
+            None => write!(f, "{}", self.problem)
+        }
+    }
+}
+
 impl serde::Serialize for TinkarosError {
     fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
     where
@@ -0,0 +1,190 @@
+use std::{collections::{BTreeMap, HashMap}, io::{self, Cursor, Read}, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::{error::{TinkarosError, ManifestProblem}, resolve::structs::{Modpack, Mod, ModIdentifier, ModVersion, Side, ContentType, StateLauncherConfigs, DEFAULT_DATAPACK_DIR, PackConfig, SCHEMA_VERSION}, state::{State, signature::sha256_hex}, update::{new_curseforge, installed::InstalledFiles}};
+
+use super::{loader_version_id, loader_version, zip_add_dir, EXPORT_OVERRIDE_DIRS};
+
+// manifest.json of a curseforge modpack export
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurseForgeManifest {
+    pub minecraft: CurseForgeMinecraft,
+    pub manifest_type: String,
+    pub manifest_version: u32,
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub author: String,
+    pub files: Vec<CurseForgeFile>,
+    pub overrides: String,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurseForgeMinecraft {
+    pub version: String,
+    pub mod_loaders: Vec<CurseForgeModLoader>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CurseForgeModLoader {
+    pub id: String, // <loader>-<version>, e.g. forge-47.2.0
+    pub primary: bool,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CurseForgeFile {
+    #[serde(rename = "projectID")]
+    pub project_id: i32,
+    #[serde(rename = "fileID")]
+    pub file_id: i32,
+    pub required: bool,
+}
+
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+// curseforge class ids of the content types, anything else is installed as a mod
+fn content_type(class_id: Option<usize>) -> ContentType {
+    match class_id {
+        Some(12) => ContentType::ResourcePack,
+        Some(6552) => ContentType::ShaderPack,
+        Some(6945) => ContentType::DataPack,
+        _ => ContentType::Mod
+    }
+}
+
+// builds a manifest out of a curseforge pack zip, the zip itself doubles as the overrides zip
+pub async fn import(source: &str, zip: &[u8]) -> Result<Modpack, TinkarosError> {
+    let manifest: CurseForgeManifest = {
+        let mut archive = zip::ZipArchive::new(Cursor::new(zip))?;
+        let mut manifest = String::new();
+        archive.by_name(MANIFEST_FILE)?.read_to_string(&mut manifest)?;
+        serde_json::from_str(&manifest)?
+    };
+
+    let loader = manifest.minecraft.mod_loaders.iter().find(|loader| loader.primary).or(manifest.minecraft.mod_loaders.first());
+    let (mod_loader, loader_version) = match loader.and_then(|loader| loader.id.split_once('-')) {
+        Some((mod_loader, loader_version)) => (mod_loader.to_string(), loader_version.to_string()),
+        None => return Err(TinkarosError::InvalidManifest(vec![ManifestProblem { mod_name: None, problem: "no mod loader in manifest.json".to_string() }]))
+    };
+
+    // the manifest only has ids, names and content types come from the projects
+    let files: Vec<&CurseForgeFile> = manifest.files.iter().collect();
+    let projects: HashMap<i32, (String, ContentType)> = match files.is_empty() {
+        true => HashMap::new(),
+        false => new_curseforge().get_mods(files.iter().map(|file| file.project_id).collect()).await
+            .map_err(|err| TinkarosError::Unknown(Box::new(err)))?
+            .into_iter()
+            .map(|project| (project.id, (project.name, content_type(project.class_id))))
+            .collect()
+    };
+
+    let mods = files.into_iter()
+        .map(|file| {
+            let (name, content) = projects.get(&file.project_id).cloned()
+                .unwrap_or_else(|| (format!("curseforge project {}", file.project_id), ContentType::Mod));
+            // files that aren't required are the pack's optional mods, off until the player turns them on
+            Mod {
+                content,
+                optional: !file.required,
+                default_enabled: false,
+                ..Mod::new(name, ModIdentifier::CurseForgeProject(file.project_id), ModVersion::CurseForgeVersionId(file.file_id), Side::Both)
+            }
+        })
+        .collect();
+
+    Ok(Modpack {
+        schema_version: SCHEMA_VERSION,
+        name: manifest.name,
+        version: manifest.version,
+        mod_loader_version: loader_version_id(&mod_loader, &loader_version, &manifest.minecraft.version),
+        mod_loader,
+        game_version: manifest.minecraft.version,
+        overrides_url: Some(source.to_string()),
+        overrides_folders: vec![manifest.overrides],
+        server_overrides_folders: Vec::new(),
+        override_files: Vec::new(),
+        changelog_url: None,
+        launcher_configs: StateLauncherConfigs::default(),
+        mods,
+        channels: BTreeMap::new(),
+        datapack_dir: DEFAULT_DATAPACK_DIR.to_string(),
+        min_tinkaros_version: None,
+        // the zip was already checked as a whole, so it can be trusted again as the overrides zip
+        hashes: BTreeMap::from([(source.to_string(), sha256_hex(zip))]),
+    })
+}
+
+// a mod that curseforge can't reference, and what happened to it instead
+#[derive(Debug, Serialize)]
+pub struct UnrepresentedMod {
+    pub name: String,
+    pub bundled: bool,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Default)]
+pub struct ExportReport {
+    pub unrepresented: Vec<UnrepresentedMod>,
+}
+
+// writes what the last update installed as a curseforge pack zip
+// curseforge can only reference its own files, everything else is bundled from the instance as overrides
+pub fn export(data: &State, pack_config: &PackConfig, target: &Path) -> Result<ExportReport, TinkarosError> {
+    let modpack = &data.modpack;
+    let instance = Path::new(&pack_config.path);
+    let installed = InstalledFiles::load_existing(instance)?;
+    let mut report = ExportReport::default();
+    let mut files = Vec::new();
+    let mut bundled = Vec::new(); // paths relative to the instance
+
+    // curseforge packs are client installs
+    for file in installed.files.into_iter().filter(|file| file.side.installs_on(Side::Client)) {
+        let path = file.path(modpack);
+        match file.pinned {
+            Some((ModIdentifier::CurseForgeProject(project_id), ModVersion::CurseForgeVersionId(file_id))) => {
+                files.push(CurseForgeFile { project_id, file_id, required: true });
+            },
+            _ if instance.join(&path).is_file() => {
+                report.unrepresented.push(UnrepresentedMod { name: file.name, bundled: true, reason: format!("not on curseforge, bundled as overrides/{path}") });
+                bundled.push(path);
+            },
+            _ => report.unrepresented.push(UnrepresentedMod { name: file.name, bundled: false, reason: "not on curseforge and not installed anymore, update before exporting to bundle it".to_string() })
+        }
+    }
+
+    let manifest = CurseForgeManifest {
+        minecraft: CurseForgeMinecraft {
+            version: modpack.game_version.clone(),
+            mod_loaders: vec![CurseForgeModLoader {
+                id: format!("{}-{}", modpack.mod_loader, loader_version(&modpack.mod_loader, &modpack.mod_loader_version, &modpack.game_version)),
+                primary: true
+            }]
+        },
+        manifest_type: "minecraftModpack".to_string(),
+        manifest_version: 1,
+        name: modpack.name.clone(),
+        version: modpack.version.clone(),
+        author: String::new(),
+        files,
+        overrides: "overrides".to_string()
+    };
+
+    let mut zip = ZipWriter::new(fs::File::create(target)?);
+    zip.start_file(MANIFEST_FILE, SimpleFileOptions::default())?;
+    serde_json::to_writer_pretty(&mut zip, &manifest)?;
+
+    for dir in EXPORT_OVERRIDE_DIRS {
+        zip_add_dir(&mut zip, &instance.join(dir), &format!("overrides/{dir}"))?;
+    }
+    for path in bundled {
+        zip.start_file(format!("overrides/{path}"), SimpleFileOptions::default())?;
+        io::copy(&mut fs::File::open(instance.join(&path))?, &mut zip)?;
+    }
+
+    zip.finish()?;
+    Ok(report)
+}
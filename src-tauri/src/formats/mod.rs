@@ -0,0 +1,80 @@
+use std::{fs::{self, File}, io::{Write, Seek}, path::Path};
+
+use reqwest::Url;
+use zip::{ZipWriter, result::ZipResult, write::SimpleFileOptions};
+
+use crate::error::TinkarosError;
+
+pub mod mrpack;
+pub mod curseforge;
+pub mod packwiz;
+
+// what kind of pack a manifest source points at, decided by its file name
+pub enum PackFormat {
+    Tinkaros,
+    Mrpack,
+    CurseForge,
+    Packwiz,
+}
+
+impl PackFormat {
+    pub fn from_source(url: &Url) -> Self {
+        match url.path() {
+            path if path.ends_with(".mrpack") => Self::Mrpack,
+            path if path.ends_with(".zip") => Self::CurseForge,
+            path if path.ends_with(packwiz::PACK_FILE) => Self::Packwiz,
+            _ => Self::Tinkaros
+        }
+    }
+}
+
+// reads a file referenced by a pack source, same schemes as manifest sources
+pub async fn fetch_bytes(url: &Url) -> Result<Vec<u8>, TinkarosError> {
+    match url.scheme() {
+        "https" => Ok(reqwest::get(url.clone()).await?.error_for_status()?.bytes().await?.to_vec()),
+        "file" => Ok(fs::read(url.to_file_path().map_err(|_| TinkarosError::ManifestSource(url.to_string()))?)?),
+        _ => Err(TinkarosError::ManifestSource(url.to_string()))
+    }
+}
+
+// instance folders bundled as overrides when exporting
+pub const EXPORT_OVERRIDE_DIRS: [&str; 2] = ["config", "defaultconfigs"];
+
+// adds every file under dir to the zip, keeping paths relative to dir under prefix
+pub fn zip_add_dir<W: Write + Seek>(zip: &mut ZipWriter<W>, dir: &Path, prefix: &str) -> ZipResult<()> {
+    if !dir.is_dir() { return Ok(()); }
+
+    for entry in fs::read_dir(dir)?.filter_map(|entry| entry.ok()) {
+        let name = format!("{prefix}/{}", entry.file_name().to_string_lossy());
+        if entry.path().is_dir() {
+            zip_add_dir(zip, &entry.path(), &name)?;
+        } else {
+            zip.start_file(name, SimpleFileOptions::default())?;
+            std::io::copy(&mut File::open(entry.path())?, zip)?;
+        }
+    }
+    Ok(())
+}
+
+// mod_loader_version is the launcher version id (what ends up in launcher_profiles.json),
+// pack formats only carry the plain loader version
+pub fn loader_version_id(mod_loader: &str, loader_version: &str, game_version: &str) -> String {
+    match mod_loader {
+        "fabric" => format!("fabric-loader-{loader_version}-{game_version}"),
+        "quilt" => format!("quilt-loader-{loader_version}-{game_version}"),
+        "forge" => format!("{game_version}-forge-{loader_version}"),
+        "neoforge" => format!("neoforge-{loader_version}"),
+        _ => loader_version.to_string()
+    }
+}
+
+pub fn loader_version<'a>(mod_loader: &str, version_id: &'a str, game_version: &str) -> &'a str {
+    let stripped = match mod_loader {
+        "fabric" => version_id.strip_prefix("fabric-loader-").and_then(|id| id.strip_suffix(&format!("-{game_version}"))),
+        "quilt" => version_id.strip_prefix("quilt-loader-").and_then(|id| id.strip_suffix(&format!("-{game_version}"))),
+        "forge" => version_id.strip_prefix(&format!("{game_version}-forge-")),
+        "neoforge" => version_id.strip_prefix("neoforge-"),
+        _ => None
+    };
+    stripped.unwrap_or(version_id)
+}
@@ -0,0 +1,208 @@
+use std::{collections::BTreeMap, io::{self, Cursor, Read}, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use zip::{ZipWriter, write::SimpleFileOptions};
+
+use crate::{error::{TinkarosError, ManifestProblem}, resolve::structs::{Modpack, Mod, ModIdentifier, ModVersion, Side, ContentType, StateLauncherConfigs, DEFAULT_DATAPACK_DIR, OverrideFile, PackConfig, SCHEMA_VERSION, modrinth_project_id}, state::{State, signature::{sha256_hex, file_hash}}, update::installed::InstalledFiles};
+
+use super::{loader_version_id, loader_version, zip_add_dir, EXPORT_OVERRIDE_DIRS};
+
+// https://support.modrinth.com/en/articles/8802351-modrinth-modpack-format-mrpack
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MrpackIndex {
+    pub format_version: u32,
+    pub game: String,
+    pub version_id: String,
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub summary: Option<String>,
+    pub files: Vec<MrpackFile>,
+    pub dependencies: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MrpackFile {
+    pub path: String,
+    pub hashes: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<MrpackEnv>,
+    pub downloads: Vec<String>,
+    pub file_size: u64,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MrpackEnv {
+    pub client: String,
+    pub server: String,
+}
+
+impl MrpackEnv {
+    fn from_side(side: Side) -> Option<Self> {
+        match side {
+            Side::Client => Some(Self { client: "required".to_string(), server: "unsupported".to_string() }),
+            Side::Server => Some(Self { client: "unsupported".to_string(), server: "required".to_string() }),
+            Side::Both => None
+        }
+    }
+
+    fn side(&self) -> Side {
+        match (self.client.as_str(), self.server.as_str()) {
+            ("unsupported", _) => Side::Server,
+            (_, "unsupported") => Side::Client,
+            _ => Side::Both
+        }
+    }
+}
+
+pub const INDEX_FILE: &str = "modrinth.index.json";
+pub const OVERRIDE_FOLDERS: [&str; 2] = ["overrides", "client-overrides"];
+pub const SERVER_OVERRIDE_FOLDERS: [&str; 2] = ["overrides", "server-overrides"];
+
+// (dependency key, tinkaros mod_loader)
+pub const LOADERS: [(&str, &str); 4] = [("fabric-loader", "fabric"), ("quilt-loader", "quilt"), ("forge", "forge"), ("neoforge", "neoforge")];
+
+// builds a manifest out of an .mrpack, the mrpack itself doubles as the overrides zip
+pub fn import(source: &str, mrpack: &[u8]) -> Result<Modpack, TinkarosError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(mrpack))?;
+    let mut index = String::new();
+    archive.by_name(INDEX_FILE)?.read_to_string(&mut index)?;
+    let index: MrpackIndex = serde_json::from_str(&index)?;
+
+    let mut problems = Vec::new();
+    let game_version = index.dependencies.get("minecraft").cloned().unwrap_or_default();
+    let (mod_loader, loader_version) = LOADERS.iter()
+        .find_map(|(key, loader)| index.dependencies.get(*key).map(|version| (loader.to_string(), version.clone())))
+        .unwrap_or_default();
+
+    let mut mods = Vec::new();
+    let mut override_files = Vec::new();
+    for file in index.files {
+        let filename = file.path.rsplit('/').next().unwrap_or(&file.path).to_string();
+        let name = filename.trim_end_matches(".jar").trim_end_matches(".zip").to_string();
+        let side = file.env.as_ref().map(MrpackEnv::side).unwrap_or_default();
+        let (sha1, sha512) = (file.hashes.get("sha1"), file.hashes.get("sha512"));
+        let url = match file.downloads.first() {
+            Some(url) => url.to_owned(),
+            None => {
+                problems.push(ManifestProblem { mod_name: Some(name), problem: "has no download url".to_string() });
+                continue;
+            }
+        };
+
+        // anything outside the content folders (configs and the like) is installed as a plain file
+        let content = match ContentType::from_path(&file.path) {
+            Some(content) if file.path.matches('/').count() == 1 => content,
+            _ => {
+                match sha512.map(|hash| ("sha512", hash)).or_else(|| sha1.map(|hash| ("sha1", hash))) {
+                    Some((hash_format, hash)) => override_files.push(OverrideFile { path: file.path, url, hash_format: hash_format.to_string(), hash: hash.to_owned(), preserve: false, side }),
+                    None => problems.push(ManifestProblem { mod_name: None, problem: format!("{} has no sha1 or sha512 hash", file.path) })
+                }
+                continue;
+            }
+        };
+
+        let (identifier, version) = match (file.downloads.iter().find_map(|url| modrinth_project_id(url)), sha1, sha512) {
+            (Some(project_id), Some(sha1), _) => (ModIdentifier::ModrinthProject(project_id), ModVersion::ModrinthVersionHash(sha1.to_owned())),
+            // other allowed hosts like github are downloaded as they are
+            (_, _, Some(sha512)) => (ModIdentifier::Url { url, filename, sha512: sha512.to_owned() }, ModVersion::Pinned),
+            _ => {
+                problems.push(ManifestProblem { mod_name: Some(name), problem: "has no sha512 hash".to_string() });
+                continue;
+            }
+        };
+        mods.push(Mod {
+            optional: file.env.as_ref().map(|env| env.client == "optional").unwrap_or(false),
+            content,
+            ..Mod::new(name, identifier, version, side)
+        });
+    }
+
+    if !problems.is_empty() {
+        return Err(TinkarosError::InvalidManifest(problems));
+    }
+
+    Ok(Modpack {
+        schema_version: SCHEMA_VERSION,
+        name: index.name,
+        version: index.version_id,
+        mod_loader_version: loader_version_id(&mod_loader, &loader_version, &game_version),
+        mod_loader,
+        game_version,
+        overrides_url: Some(source.to_string()),
+        overrides_folders: OVERRIDE_FOLDERS.iter().map(|folder| folder.to_string()).collect(),
+        server_overrides_folders: SERVER_OVERRIDE_FOLDERS.iter().map(|folder| folder.to_string()).collect(),
+        override_files,
+        changelog_url: None,
+        launcher_configs: StateLauncherConfigs::default(),
+        mods,
+        channels: BTreeMap::new(),
+        datapack_dir: DEFAULT_DATAPACK_DIR.to_string(),
+        min_tinkaros_version: None,
+        // the mrpack was already checked as a whole, so it can be trusted again as the overrides zip
+        hashes: BTreeMap::from([(source.to_string(), sha256_hex(mrpack))]),
+    })
+}
+
+// writes what the last update installed as an .mrpack, files from modrinth are referenced by their download urls
+// everything else is bundled from the instance
+pub fn export(data: &State, pack_config: &PackConfig, target: &Path) -> Result<(), TinkarosError> {
+    let modpack = &data.modpack;
+    let instance = Path::new(&pack_config.path);
+    let installed = InstalledFiles::load_existing(instance)?;
+
+    let mut files = Vec::new();
+    let mut bundled = Vec::new(); // paths relative to the instance
+    for file in installed.files {
+        let path = file.path(modpack);
+        // mrpack downloads are limited to a few hosts, forgecdn isn't one of them
+        if modrinth_project_id(&file.url).is_none() {
+            bundled.push(path);
+            continue;
+        }
+
+        let bytes = fs::read(instance.join(&path)).map_err(|_| not_installed(&path))?;
+        files.push(MrpackFile {
+            hashes: ["sha1", "sha512"].iter().filter_map(|format| Some((format.to_string(), file_hash(format, &bytes)?))).collect(),
+            env: MrpackEnv::from_side(file.side),
+            downloads: vec![file.url],
+            file_size: bytes.len() as u64,
+            path
+        });
+    }
+
+    let mut dependencies = BTreeMap::from([("minecraft".to_string(), modpack.game_version.clone())]);
+    if let Some((key, _)) = LOADERS.iter().find(|(_, loader)| *loader == modpack.mod_loader) {
+        dependencies.insert(key.to_string(), loader_version(&modpack.mod_loader, &modpack.mod_loader_version, &modpack.game_version).to_string());
+    }
+
+    let index = MrpackIndex {
+        format_version: 1,
+        game: "minecraft".to_string(),
+        version_id: modpack.version.clone(),
+        name: modpack.name.clone(),
+        summary: None,
+        files,
+        dependencies
+    };
+
+    let mut zip = ZipWriter::new(fs::File::create(target)?);
+    zip.start_file(INDEX_FILE, SimpleFileOptions::default())?;
+    serde_json::to_writer_pretty(&mut zip, &index)?;
+
+    for dir in EXPORT_OVERRIDE_DIRS {
+        zip_add_dir(&mut zip, &instance.join(dir), &format!("overrides/{dir}"))?;
+    }
+    for path in bundled {
+        zip.start_file(format!("overrides/{path}"), SimpleFileOptions::default())?;
+        io::copy(&mut fs::File::open(instance.join(&path)).map_err(|_| not_installed(&path))?, &mut zip)?;
+    }
+
+    zip.finish()?;
+    Ok(())
+}
+
+fn not_installed(path: &str) -> TinkarosError {
+    TinkarosError::Update(format!("{path} is not installed anymore, update before exporting"))
+}
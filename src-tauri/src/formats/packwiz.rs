@@ -0,0 +1,259 @@
+use std::collections::{BTreeMap, HashMap};
+
+use ferinth::structures::version::Version;
+use reqwest::Url;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{error::{TinkarosError, ManifestProblem}, resolve::structs::{Modpack, Mod, ModIdentifier, ModVersion, Side, ContentType, StateLauncherConfigs, DEFAULT_DATAPACK_DIR, OverrideFile, SCHEMA_VERSION}, state::signature::file_hash};
+
+use super::{loader_version_id, fetch_bytes};
+
+// https://packwiz.infra.link/reference/pack-format/
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct PackToml {
+    name: String,
+    #[serde(default)]
+    version: String,
+    index: IndexRef,
+    versions: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct IndexRef {
+    file: String,
+    hash_format: String,
+    hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct IndexToml {
+    hash_format: String,
+    #[serde(default)]
+    files: Vec<IndexFile>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct IndexFile {
+    file: String,
+    hash: String,
+    hash_format: Option<String>,
+    alias: Option<String>,
+    #[serde(default)]
+    metafile: bool,
+    #[serde(default)]
+    preserve: bool,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ModToml {
+    name: String,
+    #[serde(default)]
+    side: Side,
+    download: ModDownload,
+    #[serde(default)]
+    update: ModUpdate,
+    #[serde(default)]
+    option: ModOption,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ModOption {
+    #[serde(default)]
+    optional: bool,
+    description: Option<String>,
+    #[serde(default)]
+    default: bool,
+}
+
+impl ModOption {
+    fn apply(self, mod_: Mod) -> Mod {
+        Mod { optional: self.optional, description: self.description, default_enabled: self.default, ..mod_ }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ModDownload {
+    hash_format: String,
+    hash: String,
+}
+
+#[derive(Debug, Deserialize, Default)]
+struct ModUpdate {
+    modrinth: Option<ModrinthUpdate>,
+    curseforge: Option<CurseForgeUpdate>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ModrinthUpdate {
+    mod_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct CurseForgeUpdate {
+    project_id: i32,
+    file_id: i32,
+}
+
+// packwiz keys in [versions], minecraft is handled separately
+const LOADERS: [&str; 4] = ["fabric", "quilt", "forge", "neoforge"];
+
+pub const PACK_FILE: &str = "pack.toml";
+
+// a packwiz source can also be the pack's directory, on disk or as a url ending in /
+pub fn pack_file_url(url: Url) -> Url {
+    let is_dir = match url.scheme() {
+        "file" => url.to_file_path().map(|path| path.is_dir()).unwrap_or(false),
+        _ => url.path().ends_with('/')
+    };
+    if !is_dir { return url; }
+
+    let mut dir = url;
+    if !dir.path().ends_with('/') {
+        let path = format!("{}/", dir.path());
+        dir.set_path(&path);
+    }
+    dir.join(PACK_FILE).unwrap_or(dir)
+}
+
+// builds a manifest from a packwiz pack, source points at pack.toml either on disk or over http
+// every file is checked against the hash of the file that lists it, so trusting pack.toml trusts the whole pack
+pub async fn import(url: &Url, pack_toml: &[u8]) -> Result<Modpack, TinkarosError> {
+    let pack: PackToml = toml::from_str(&String::from_utf8_lossy(pack_toml)).map_err(|err| TinkarosError::DataInvalid(err.to_string()))?;
+    let mut problems = Vec::new();
+
+    let index_url = url.join(&pack.index.file).map_err(|err| TinkarosError::DataInvalid(err.to_string()))?;
+    let index_bytes = fetch_bytes(&index_url).await?;
+    check_hash(&pack.index.file, &pack.index.hash_format, &pack.index.hash, &index_bytes)?;
+    let index: IndexToml = toml::from_str(&String::from_utf8_lossy(&index_bytes)).map_err(|err| TinkarosError::DataInvalid(err.to_string()))?;
+
+    let mut mods = Vec::new();
+    let mut override_files = Vec::new();
+    // modrinth metafiles usually only carry sha512, mod versions are looked up by sha1
+    let mut modrinth_sha512s = Vec::new();
+
+    for file in index.files {
+        let hash_format = file.hash_format.as_deref().unwrap_or(&index.hash_format);
+        let file_url = index_url.join(&file.file).map_err(|err| TinkarosError::DataInvalid(err.to_string()))?;
+
+        if !file.metafile {
+            override_files.push(OverrideFile {
+                path: file.alias.unwrap_or(file.file),
+                url: file_url.to_string(),
+                hash_format: hash_format.to_string(),
+                hash: file.hash,
+                preserve: file.preserve,
+                side: Side::Both
+            });
+            continue;
+        }
+
+        let bytes = fetch_bytes(&file_url).await?;
+        check_hash(&file.file, hash_format, &file.hash, &bytes)?;
+        let metafile: ModToml = toml::from_str(&String::from_utf8_lossy(&bytes)).map_err(|err| TinkarosError::DataInvalid(format!("{}: {err}", file.file)))?;
+
+        // packwiz keeps metafiles in the folder their file is installed to
+        let content = match ContentType::from_path(&file.file) {
+            Some(content) => content,
+            None => {
+                problems.push(ManifestProblem { mod_name: Some(metafile.name), problem: format!("{} is not in mods/, resourcepacks/, shaderpacks/ or {DEFAULT_DATAPACK_DIR}/", file.file) });
+                continue;
+            }
+        };
+
+        match (metafile.update.modrinth, metafile.update.curseforge) {
+            (Some(modrinth), _) => {
+                match metafile.download.hash_format.as_str() {
+                    "sha1" => {},
+                    "sha512" => modrinth_sha512s.push(metafile.download.hash.clone()),
+                    hash_format => {
+                        problems.push(ManifestProblem { mod_name: Some(metafile.name), problem: format!("{} has a {hash_format} hash, modrinth versions can only be found by sha1 or sha512", file.file) });
+                        continue;
+                    }
+                }
+                mods.push(Mod { content, ..metafile.option.apply(Mod::new(metafile.name, ModIdentifier::ModrinthProject(modrinth.mod_id), ModVersion::ModrinthVersionHash(metafile.download.hash), metafile.side)) });
+            },
+            (None, Some(curseforge)) => mods.push(Mod { content, ..metafile.option.apply(Mod::new(metafile.name, ModIdentifier::CurseForgeProject(curseforge.project_id), ModVersion::CurseForgeVersionId(curseforge.file_id), metafile.side)) }),
+            (None, None) => problems.push(ManifestProblem { mod_name: Some(metafile.name), problem: "not on modrinth or curseforge".to_string() })
+        }
+    }
+
+    if !modrinth_sha512s.is_empty() {
+        let sha1s = modrinth_sha1s(modrinth_sha512s).await?;
+        for mod_ in &mut mods {
+            if let ModVersion::ModrinthVersionHash(hash) = &mut mod_.version {
+                if hash.len() == 128 {
+                    match sha1s.get(hash.as_str()) {
+                        Some(sha1) => *hash = sha1.to_owned(),
+                        None => problems.push(ManifestProblem { mod_name: Some(mod_.name.clone()), problem: "file not found on modrinth".to_string() })
+                    }
+                }
+            }
+        }
+    }
+
+    if !problems.is_empty() {
+        return Err(TinkarosError::InvalidManifest(problems));
+    }
+
+    let game_version = pack.versions.get("minecraft").cloned().unwrap_or_default();
+    let (mod_loader, loader_version) = LOADERS.iter()
+        .find_map(|loader| pack.versions.get(*loader).map(|version| (loader.to_string(), version.clone())))
+        .unwrap_or_default();
+
+    Ok(Modpack {
+        schema_version: SCHEMA_VERSION,
+        name: pack.name,
+        version: pack.version,
+        mod_loader_version: loader_version_id(&mod_loader, &loader_version, &game_version),
+        mod_loader,
+        game_version,
+        overrides_url: None,
+        overrides_folders: Vec::new(),
+        server_overrides_folders: Vec::new(),
+        override_files,
+        changelog_url: None,
+        launcher_configs: StateLauncherConfigs::default(),
+        mods,
+        channels: BTreeMap::new(),
+        datapack_dir: DEFAULT_DATAPACK_DIR.to_string(),
+        min_tinkaros_version: None,
+        hashes: BTreeMap::new(),
+    })
+}
+
+fn check_hash(file: &str, hash_format: &str, expected: &str, bytes: &[u8]) -> Result<(), TinkarosError> {
+    match file_hash(hash_format, bytes) {
+        Some(hash) if hash.eq_ignore_ascii_case(expected) => Ok(()),
+        Some(_) => Err(TinkarosError::FileHashMismatch(file.to_string())),
+        None => Err(TinkarosError::DataInvalid(format!("{file}: unsupported hash format {hash_format}")))
+    }
+}
+
+// sha512 -> sha1 of the same modrinth files
+async fn modrinth_sha1s(sha512s: Vec<String>) -> Result<HashMap<String, String>, TinkarosError> {
+    let versions: HashMap<String, Version> = reqwest::Client::new()
+        .post("https://api.modrinth.com/v2/version_files")
+        .header("User-Agent", "Tinkaros")
+        .json(&json!({ "hashes": sha512s, "algorithm": "sha512" }))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+
+    Ok(versions.into_iter()
+        .filter_map(|(sha512, version)| {
+            let file = version.files.into_iter().find(|file| file.hashes.sha512 == sha512)?;
+            Some((sha512, file.hashes.sha1))
+        })
+        .collect())
+}
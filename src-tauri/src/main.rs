@@ -2,34 +2,68 @@
 
 pub mod update;
 pub mod resolve;
+pub mod formats;
+pub mod sources;
 pub mod state;
 pub mod error;
 
 use std::{fs::{self, File}, path::PathBuf, env::consts, process::Command, time::{SystemTime, UNIX_EPOCH}};
 use error::TinkarosError;
 use serde::{Serialize, Deserialize};
-use resolve::{structs::{AppConfig, ModIdentifier, GithubRelease}, config::{write_config, get_config}};
+use resolve::{structs::{AppConfig, PackConfig, Mod, GithubRelease, DEFAULT_CHANNEL}, config::{write_config, get_config, get_pack_config, validate_pack_id}};
 use state::State;
-use update::{mods::{update_mods, get_projects_from_ids}, status::{update_progress, update_status}, configs::resolve_configs, structs::CombinedProjects};
+use formats::{mrpack, curseforge::{self, ExportReport}};
+use sources::sources;
+use update::{download_cache, mods::update_mods, status::{update_progress, update_status}, configs::resolve_configs, structs::{CombinedProjects, UpdateReport}};
 
 use crate::resolve::config::get_launchers;
 
+const TINKAROS_REPO: &str = "evergales/tinkaros";
+
 #[derive(Serialize, Deserialize, Default)]
 struct VersionFile {
   version: String,
-  last_updated: u64
+  last_updated: u64,
+  // files written before channels existed are on the stable channel
+  #[serde(default = "default_channel")]
+  channel: String
 }
 
+fn default_channel() -> String { DEFAULT_CHANNEL.to_string() }
+
 #[derive(Serialize)]
 struct VersionRes {
   version: String,
   latest_version: String,
-  last_updated: u64
+  last_updated: u64,
+  offline: bool,
+  channel: String,
+  installed_channel: String,
+  missing_channel: Option<String>
+}
+
+#[tauri::command]
+fn init(pack: String, chosen: String, path: String, manifest_source: Option<String>) -> Result<(), TinkarosError> {
+  validate_pack_id(&pack)?;
+  let old = get_config()?;
+  let mut config = if old.init { old } else {
+    let mut config = AppConfig::new(true, true, 75, false);
+    config.manifest_poll_interval = old.manifest_poll_interval;
+    config.packs = old.packs;
+    config
+  };
+
+  config.packs.insert(pack, PackConfig::new(chosen, path, manifest_source));
+  write_config(config)?;
+  Ok(())
 }
 
 #[tauri::command]
-fn init(chosen: String, path: String) -> Result<(), TinkarosError> {
-  write_config(AppConfig::new(true, chosen, path, true, 75, false))?;
+async fn remove_pack(pack: String) -> Result<(), TinkarosError> {
+  let mut config = get_config()?;
+  config.packs.remove(&pack).ok_or_else(|| TinkarosError::UnknownPack(pack.clone()))?;
+  write_config(config)?;
+  State::unload(&pack).await;
   Ok(())
 }
 
@@ -43,55 +77,70 @@ async fn check_online() -> Result<(), TinkarosError> {
 }
 
 #[tauri::command]
-async fn update(app: tauri::AppHandle, launcher: String, path: String) -> Result<(), TinkarosError> {
+async fn update(app: tauri::AppHandle, pack: String) -> Result<UpdateReport, TinkarosError> {
   check_online().await?;
-  let path = PathBuf::from(path);
+  let config = get_pack_config(&pack)?;
+  let data = State::get(&pack).await?;
+  if let Some(required) = &data.modpack.min_tinkaros_version {
+    check_min_tinkaros_version(&app, required).await?;
+  }
+
+  let path = PathBuf::from(&config.path);
   fs::create_dir_all(path.join("mods"))?;
 
   update_status("preparing", &app)?;
-  update_mods(&path, &app).await?;
+  let mut report = update_mods(&data, &config, &app).await?;
+  if let Some(channel) = &data.missing_channel {
+    report.warnings.push(format!("the pack has no {channel} channel anymore, updating to {DEFAULT_CHANNEL} instead"));
+  }
+  // configs belong to the new version, which isn't fully there yet
+  if !report.failed.is_empty() {
+    update_status("some downloads failed", &app)?;
+    return Ok(report);
+  }
   
   update_status("adding required configs", &app)?;
-  resolve_configs(&app, &path, launcher).await?;
+  resolve_configs(&app, &data, &path, config.launcher.clone(), config.side()).await?;
 
   update_status("done!", &app)?;
   update_progress(100, &app)?;
 
-  Ok(())
+  Ok(report)
 }
 
-#[tauri::command]
-async fn list_mod_projects(limit: usize, app: tauri::AppHandle) -> Result<Vec<CombinedProjects>, TinkarosError> {
-  let data = State::get().await?;
+// direct downloads have no project page, a page can come back short with more mods after it
+#[derive(Serialize)]
+struct ModProjectsPage {
+  projects: Vec<CombinedProjects>,
+  more: bool
+}
 
-  let mut modrinth_ids: Vec<String> = Vec::new();
-  let mut curseforge_ids: Vec<i32> = Vec::new();
+#[tauri::command]
+async fn list_mod_projects(pack: String, limit: usize, app: tauri::AppHandle) -> Result<ModProjectsPage, TinkarosError> {
+  let data = State::get(&pack).await?;
+  let sources = sources(&app)?;
 
   let lower_bound = std::cmp::max(limit as i32 - 25, 0) as usize;
   let upper_bound = std::cmp::min(limit, data.modpack.mods.len());
+  // the manifest can shrink between pages, past the end is an empty last page
+  let lower_bound = std::cmp::min(lower_bound, upper_bound);
+  let mods: Vec<&Mod> = data.modpack.mods[lower_bound..upper_bound].iter().collect();
 
-  for mod_item in &data.modpack.mods[lower_bound..upper_bound] {
-      match &mod_item.identifier {
-          ModIdentifier::ModrinthProject(id) => modrinth_ids.push(id.clone()),
-          ModIdentifier::CurseForgeProject(id) => curseforge_ids.push(id.to_owned()),
-      }
-  }
-
-  let mut result = get_projects_from_ids(modrinth_ids, curseforge_ids, &app).await?;
+  let mut result = sources::projects(&sources, &mods).await?;
 
   result.sort_by_key(|project| match project {
     CombinedProjects::ModrinthProject(project) => project.slug.to_owned(),
     CombinedProjects::CurseForgeMod(mod_) => mod_.slug.to_owned()
   });
 
-  Ok(result)
+  Ok(ModProjectsPage { projects: result, more: upper_bound < data.modpack.mods.len() })
 }
 
 #[tauri::command]
-async fn log_update(path: String) -> Result<(), TinkarosError> {
+async fn log_update(pack: String) -> Result<(), TinkarosError> {
   let mut should_add_comment = false;
   
-  let file = PathBuf::from(path).join("version.toml");
+  let file = PathBuf::from(get_pack_config(&pack)?.path).join("version.toml");
   if !file.exists() {
     File::create(&file)?;
     should_add_comment = true;
@@ -100,7 +149,9 @@ async fn log_update(path: String) -> Result<(), TinkarosError> {
   let str_file = fs::read_to_string(&file)?;
   let mut data: VersionFile = toml::from_str(&str_file).unwrap_or_default();
 
-  data.version = State::get().await.unwrap().modpack.version.clone();
+  let state = State::get(&pack).await?;
+  data.version.clone_from(&state.modpack.version);
+  data.channel.clone_from(&state.channel);
   data.last_updated = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
 
   let mut toml_string = toml::to_string(&data).unwrap();
@@ -111,16 +162,17 @@ async fn log_update(path: String) -> Result<(), TinkarosError> {
 }
 
 #[tauri::command]
-async fn get_version(path: String) -> Result<VersionRes, TinkarosError> {
-  let file = PathBuf::from(path).join("version.toml");
-  let latest = State::get().await?.modpack.version.clone();
+async fn get_version(pack: String) -> Result<VersionRes, TinkarosError> {
+  let file = PathBuf::from(get_pack_config(&pack)?.path).join("version.toml");
+  let state = State::get(&pack).await?;
+  let latest = state.modpack.version.clone();
   
   if file.exists() {
     let str_file = fs::read_to_string(&file)?;
     let file_data: VersionFile = toml::from_str(&str_file).unwrap_or_default();
-    Ok(VersionRes { version: file_data.version, latest_version: latest, last_updated: file_data.last_updated })
+    Ok(VersionRes { version: file_data.version, latest_version: latest, last_updated: file_data.last_updated, offline: state.offline, channel: state.channel.clone(), installed_channel: file_data.channel, missing_channel: state.missing_channel.clone() })
   } else {
-    Ok(VersionRes { version: "not installed".to_string(), latest_version: latest, last_updated: 0 })
+    Ok(VersionRes { version: "not installed".to_string(), latest_version: latest, last_updated: 0, offline: state.offline, channel: state.channel.clone(), installed_channel: String::new(), missing_channel: state.missing_channel.clone() })
   }
 }
 
@@ -146,27 +198,96 @@ fn check_modpack_installed(path: &str) -> Result<bool, TinkarosError> {
 
 #[tauri::command]
 async fn check_tinkaros_update(app: tauri::AppHandle) -> Result<bool, TinkarosError> {
-  let res_str = reqwest::Client::new()
-    .get("https://api.github.com/repos/evergales/tinkaros/releases")
-    .header("User-Agent", "Tinkaros")
-    .send()
-    .await?
-    .text()
-    .await?;
-  let res_json: Vec<GithubRelease> = serde_json::from_str(&res_str)?;
+  let res_json = GithubRelease::list(TINKAROS_REPO).await?;
   Ok(tauri::api::version::is_greater(app.package_info().version.to_string().as_str(), res_json.first().unwrap().tag_name.as_str()).unwrap())
 }
 
+// packs can require a newer tinkaros when they start using manifest features older builds don't know
+async fn check_min_tinkaros_version(app: &tauri::AppHandle, required: &str) -> Result<(), TinkarosError> {
+  let required = required.trim_start_matches('v');
+  if !tauri::api::version::is_greater(app.package_info().version.to_string().as_str(), required).unwrap_or(false) {
+    return Ok(());
+  }
+
+  let latest = GithubRelease::list(TINKAROS_REPO).await.ok().and_then(|releases| releases.into_iter().next()).map(|release| release.tag_name);
+  Err(TinkarosError::TinkarosOutdated { required: required.to_string(), latest })
+}
+
+#[tauri::command]
+async fn export_mrpack(pack: String, target: String) -> Result<(), TinkarosError> {
+  let config = get_pack_config(&pack)?;
+  let data = State::get(&pack).await?;
+  // hashes the whole instance, TinkarosError isn't Send so it comes back as a message
+  tauri::async_runtime::spawn_blocking(move || mrpack::export(&data, &config, &PathBuf::from(target)).map_err(|err| err.to_string())).await
+    .map_err(|err| TinkarosError::Update(err.to_string()))?
+    .map_err(TinkarosError::Update)
+}
+
+#[tauri::command]
+async fn export_curseforge(pack: String, target: String) -> Result<ExportReport, TinkarosError> {
+  let config = get_pack_config(&pack)?;
+  let data = State::get(&pack).await?;
+  // copies the whole instance into the zip, same as export_mrpack
+  tauri::async_runtime::spawn_blocking(move || curseforge::export(&data, &config, &PathBuf::from(target)).map_err(|err| err.to_string())).await
+    .map_err(|err| TinkarosError::Update(err.to_string()))?
+    .map_err(TinkarosError::Update)
+}
+
+#[derive(Serialize)]
+struct OptionalMod {
+  name: String,
+  description: Option<String>,
+  enabled: bool
+}
+
+#[tauri::command]
+async fn list_optional_mods(pack: String) -> Result<Vec<OptionalMod>, TinkarosError> {
+  let config = get_pack_config(&pack)?;
+  let data = State::get(&pack).await?;
+  Ok(data.modpack.mods.iter()
+    .filter(|mod_| mod_.optional && mod_.side.installs_on(config.side()))
+    .map(|mod_| OptionalMod { name: mod_.name.clone(), description: mod_.description.clone(), enabled: mod_.enabled(&config.optional_mods) })
+    .collect())
+}
+
+// the top level channel first, then whatever else the manifest declares
+#[tauri::command]
+async fn list_channels(pack: String) -> Result<Vec<String>, TinkarosError> {
+  let data = State::get(&pack).await?;
+  Ok(std::iter::once(DEFAULT_CHANNEL.to_string()).chain(data.modpack.channels.keys().cloned()).collect())
+}
+
+#[tauri::command]
+async fn reload_manifest(pack: String, app: tauri::AppHandle) -> Result<String, TinkarosError> {
+  Ok(State::reload(&pack, &app).await?.modpack.version.clone())
+}
+
+#[tauri::command]
+fn download_cache_size() -> Result<u64, TinkarosError> {
+  download_cache::size()
+}
+
+// returns the bytes freed
+#[tauri::command]
+async fn prune_download_cache() -> Result<u64, TinkarosError> {
+  download_cache::prune().await
+}
+
 #[tauri::command]
-async fn get_modpack_changelog() -> Result<String, TinkarosError> {
-  let changelog = &State::get().await?.modpack.changelog_url;
-  Ok(changelog.to_string())
+async fn get_modpack_changelog(pack: String) -> Result<Option<String>, TinkarosError> {
+  let changelog = &State::get(&pack).await?.modpack.changelog_url;
+  Ok(changelog.clone())
 }
 
 fn main() {
     tauri::Builder::default()
+        .setup(|app| {
+          tauri::async_runtime::spawn(State::poll(app.handle()));
+          Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
           init,
+          remove_pack,
           get_config,
           write_config,
           get_launchers,
@@ -174,7 +295,14 @@ fn main() {
           log_update,
           get_version,
           get_modpack_changelog,
+          reload_manifest,
+          export_mrpack,
+          export_curseforge,
           list_mod_projects, 
+          list_optional_mods,
+          list_channels,
+          download_cache_size,
+          prune_download_cache,
           explorer,
           check_modpack_installed,
           check_tinkaros_update,
@@ -1,9 +1,9 @@
-use std::fs;
+use std::{fs, env};
 use tauri::{Config, api::path};
 
 use crate::{error::TinkarosError, state::State};
 
-use super::structs::{AppConfig, LauncherPath, Launcher};
+use super::structs::{AppConfig, LauncherPath, Launcher, PackConfig, DEFAULT_PACK};
 
 #[tauri::command]
 pub fn get_config() -> Result<AppConfig, TinkarosError> {
@@ -14,7 +14,7 @@ pub fn get_config() -> Result<AppConfig, TinkarosError> {
     }
     let file = fs::read_to_string(&config)?;
     let confs: AppConfig = toml::from_str(&file).unwrap_or_default();
-    Ok(confs)
+    Ok(confs.migrate())
 }
 
 #[tauri::command]
@@ -30,24 +30,80 @@ pub fn write_config(config: AppConfig) -> Result<AppConfig, TinkarosError> {
     Ok(config)
 }
 
+pub fn get_pack_config(pack: &str) -> Result<PackConfig, TinkarosError> {
+    get_config()?.packs.remove(pack).ok_or_else(|| TinkarosError::UnknownPack(pack.to_string()))
+}
+
+// pack ids end up in file names and env var names
+pub fn validate_pack_id(pack: &str) -> Result<(), TinkarosError> {
+    match !pack.is_empty() && pack.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+        true => Ok(()),
+        false => Err(TinkarosError::InvalidPackId(pack.to_string()))
+    }
+}
+
+const DEFAULT_MANIFEST_SOURCE: &str = "https://gist.githubusercontent.com/evergales/ec9d01d863083becd062b378ca01a3d8/raw/ahms.json";
+
+// where to load a pack's manifest from
+// priority: --manifest flag > TINKAROS_MANIFEST env var > pack config > default gist (default pack only)
+// `--manifest <source>` and TINKAROS_MANIFEST apply to the default pack,
+// `--manifest <pack>=<source>` and TINKAROS_MANIFEST_<PACK> to any other
+pub fn get_manifest_source(pack: &str) -> Result<String, TinkarosError> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = if arg == "--manifest" {
+            args.next().ok_or_else(|| TinkarosError::ManifestSource("--manifest requires a value".to_string()))?
+        } else if let Some(value) = arg.strip_prefix("--manifest=") {
+            value.to_string()
+        } else {
+            continue;
+        };
+
+        // sources always contain a ':' so they never look like a pack id
+        match value.split_once('=') {
+            Some((id, source)) if validate_pack_id(id).is_ok() => if id == pack { return Ok(source.to_string()); },
+            _ => if pack == DEFAULT_PACK { return Ok(value); }
+        }
+    }
+
+    let env_name = match pack {
+        DEFAULT_PACK => "TINKAROS_MANIFEST".to_string(),
+        _ => format!("TINKAROS_MANIFEST_{}", pack.to_uppercase().replace('-', "_"))
+    };
+    if let Ok(source) = env::var(env_name) {
+        if !source.is_empty() { return Ok(source); }
+    }
+
+    match get_config()?.packs.remove(pack).and_then(|pack| pack.manifest_source) {
+        Some(source) if !source.is_empty() => Ok(source),
+        _ if pack == DEFAULT_PACK => Ok(DEFAULT_MANIFEST_SOURCE.to_string()),
+        _ => Err(TinkarosError::ManifestSource(format!("no manifest source set for pack {pack}")))
+    }
+}
+
 #[tauri::command]
-pub async fn get_launchers() -> Vec<Launcher> {
+pub async fn get_launchers(pack: String, manifest_source: Option<String>) -> Result<Vec<Launcher>, TinkarosError> {
   let mut found: Vec<Launcher> = Vec::new();
+  let modpack_name = match manifest_source.filter(|source| !source.is_empty()) {
+    // packs that are still being added aren't in the config yet
+    Some(source) => State::preview(&pack, &source).await?.modpack.name,
+    None => State::get(&pack).await?.modpack.name.clone()
+  };
 
   if LauncherPath::mclauncher().await.exists() {
-    let path = path::app_config_dir(&Config::default()).unwrap().join(format!("tinkaros/{}", State::get().await.unwrap().modpack.name));
+    let path = path::app_config_dir(&Config::default()).unwrap().join(format!("tinkaros/{}", modpack_name));
     found.push(Launcher::new("default".to_string(), path.to_string_lossy().to_string()));
   }
 
   if LauncherPath::curseforge().await.exists() {
-    let path = LauncherPath::curseforge_instance().await;
+    let path = LauncherPath::curseforge_instance(&modpack_name).await;
     found.push(Launcher::new("curseforge".to_string(), path.to_string_lossy().to_string()))
   }
 
   if LauncherPath::prism().await.exists() {
-    let path = LauncherPath::prism_instance().await;
+    let path = LauncherPath::prism_instance(&modpack_name).await;
     found.push(Launcher::new("prism".to_string(), path.to_string_lossy().to_string()))
   }
 
-  found
-}
\ No newline at end of file
+  Ok(found)
+}
@@ -1,2 +1,4 @@
 pub mod structs;
-pub mod config;
\ No newline at end of file
+pub mod config;
+pub mod validate;
+pub mod overrides;
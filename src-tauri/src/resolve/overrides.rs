@@ -0,0 +1,217 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use reqwest::Url;
+use serde::Deserialize;
+
+use crate::{error::TinkarosError, state::signature::file_hash};
+
+use super::structs::{Mod, ModIdentifier, ModVersion, Side, ContentType};
+
+// lives in the instance, merged over the manifest on every update
+pub const USER_OVERRIDES_FILE: &str = "tinkaros-user.toml";
+
+#[derive(Debug, Deserialize, Default)]
+#[serde(default)]
+pub struct UserOverrides {
+    pub add: Vec<UserMod>,
+    pub exclude: Vec<String>, // manifest mod names
+    pub pins: BTreeMap<String, UserVersion>, // manifest mod name -> version
+}
+
+// exactly one of modrinth, curseforge or file
+#[derive(Debug, Deserialize)]
+pub struct UserMod {
+    pub name: String,
+    pub modrinth: Option<String>,
+    pub curseforge: Option<i32>,
+    pub file: Option<String>, // local jar, relative to the instance
+    pub version: Option<UserVersion>, // newest compatible when left out
+    #[serde(default)]
+    pub content: ContentType, // resource packs, shaders and datapacks go to their own folders
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum UserVersion {
+    ModrinthVersionHash(String),
+    CurseForgeFileId(i32),
+}
+
+fn invalid(name: &str, problem: &str) -> TinkarosError {
+    TinkarosError::DataInvalid(format!("{USER_OVERRIDES_FILE}: {name}: {problem}"))
+}
+
+// the version has to match the source, a curseforge file id can't pin a modrinth mod
+fn pinned_version(name: &str, identifier: &ModIdentifier, version: UserVersion) -> Result<ModVersion, TinkarosError> {
+    match (identifier, version) {
+        (ModIdentifier::ModrinthProject(_), UserVersion::ModrinthVersionHash(hash)) => Ok(ModVersion::ModrinthVersionHash(hash)),
+        (ModIdentifier::CurseForgeProject(_), UserVersion::CurseForgeFileId(id)) => Ok(ModVersion::CurseForgeVersionId(id)),
+        (ModIdentifier::ModrinthProject(_), _) => Err(invalid(name, "modrinth mods are pinned by the sha1 of the file")),
+        (ModIdentifier::CurseForgeProject(_), _) => Err(invalid(name, "curseforge mods are pinned by file id")),
+        _ => Err(invalid(name, "only modrinth and curseforge mods can be pinned"))
+    }
+}
+
+impl UserMod {
+    fn into_mod(self, instance: &Path) -> Result<Mod, TinkarosError> {
+        let (identifier, version) = match (self.modrinth, self.curseforge, self.file) {
+            (Some(id), None, None) => (ModIdentifier::ModrinthProject(id), None),
+            (None, Some(id), None) => (ModIdentifier::CurseForgeProject(id), None),
+            // local jars are copied in through a file:// url, hashed now so the usual url check applies
+            (None, None, Some(file)) => {
+                let path = instance.join(file);
+                let bytes = fs::read(&path).map_err(|_| invalid(&self.name, &format!("{} can't be read", path.to_string_lossy())))?;
+                let identifier = ModIdentifier::Url {
+                    url: Url::from_file_path(&path).map_err(|_| invalid(&self.name, "file is not an absolute path"))?.to_string(),
+                    filename: path.file_name().map(|name| name.to_string_lossy().to_string()).ok_or_else(|| invalid(&self.name, "file has no name"))?,
+                    sha512: file_hash("sha512", &bytes).unwrap()
+                };
+                (identifier, Some(ModVersion::Pinned))
+            },
+            _ => return Err(invalid(&self.name, "needs exactly one of modrinth, curseforge or file"))
+        };
+
+        let version = match (version, self.version) {
+            (Some(version), None) => version,
+            (Some(_), Some(_)) => return Err(invalid(&self.name, "local files can't have a version")),
+            (None, Some(pin)) => pinned_version(&self.name, &identifier, pin)?,
+            (None, None) => ModVersion::Latest
+        };
+
+        Ok(Mod { locked: true, content: self.content, ..Mod::new(self.name, identifier, version, Side::Both) })
+    }
+}
+
+impl UserOverrides {
+    pub fn load(instance: &Path) -> Result<Self, TinkarosError> {
+        let path = instance.join(USER_OVERRIDES_FILE);
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        toml::from_str(&fs::read_to_string(path)?).map_err(|err| TinkarosError::DataInvalid(format!("{USER_OVERRIDES_FILE}: {err}")))
+    }
+
+    // manifest mods the user left out, resolve keeps them from coming back in as dependencies
+    pub fn excluded<'a>(&self, mods: &'a [Mod]) -> Vec<&'a Mod> {
+        mods.iter().filter(|mod_| self.exclude.contains(&mod_.name)).collect()
+    }
+
+    // names that aren't in the manifest (anymore) are ignored, packs change under the user
+    pub fn apply(self, instance: &Path, mods: Vec<Mod>) -> Result<Vec<Mod>, TinkarosError> {
+        let mut merged = Vec::new();
+        for mod_ in mods {
+            if self.exclude.contains(&mod_.name) { continue; }
+            match self.pins.get(&mod_.name) {
+                Some(pin) => merged.push(Mod { version: pinned_version(&mod_.name, &mod_.identifier, pin.clone())?, locked: true, ..mod_ }),
+                None => merged.push(mod_)
+            }
+        }
+
+        for added in self.add {
+            merged.push(added.into_mod(instance)?);
+        }
+        Ok(merged)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use crate::{resolve::structs::{Mod, ModIdentifier, ModVersion, Side, ContentType}, state::signature::file_hash};
+
+    use super::UserOverrides;
+
+    fn manifest_mods() -> Vec<Mod> {
+        vec![
+            Mod::new("Sodium".to_string(), ModIdentifier::ModrinthProject("AANobbMI".to_string()), ModVersion::ModrinthVersionHash("old".to_string()), Side::Both),
+            Mod::new("JEI".to_string(), ModIdentifier::CurseForgeProject(238222), ModVersion::CurseForgeVersionId(1), Side::Both),
+            Mod::new("Iris Shaders".to_string(), ModIdentifier::ModrinthProject("YL57xq9U".to_string()), ModVersion::ModrinthVersionHash("iris".to_string()), Side::Client),
+        ]
+    }
+
+    fn apply(overrides: &str, instance: &Path) -> Vec<Mod> {
+        toml::from_str::<UserOverrides>(overrides).unwrap().apply(instance, manifest_mods()).unwrap()
+    }
+
+    #[test]
+    fn nothing_to_override() {
+        let mods = apply("", Path::new("/instance"));
+        assert_eq!(mods.len(), 3);
+        assert!(mods.iter().all(|mod_| !mod_.locked));
+    }
+
+    #[test]
+    fn excludes_and_pins_manifest_mods() {
+        let mods = apply(r#"
+            exclude = ["Iris Shaders", "not in the manifest"]
+
+            [pins]
+            Sodium = "abc123"
+            JEI = 42
+            "also not in the manifest" = 1
+        "#, Path::new("/instance"));
+
+        assert_eq!(mods.iter().map(|mod_| mod_.name.as_str()).collect::<Vec<_>>(), ["Sodium", "JEI"]);
+        assert!(matches!(&mods[0].version, ModVersion::ModrinthVersionHash(hash) if hash == "abc123"));
+        assert!(matches!(mods[1].version, ModVersion::CurseForgeVersionId(42)));
+        assert!(mods.iter().all(|mod_| mod_.locked));
+    }
+
+    #[test]
+    fn adds_mods() {
+        let instance = std::env::temp_dir().join(format!("tinkaros-overrides-{}", std::process::id()));
+        fs::create_dir_all(instance.join("extra")).unwrap();
+        fs::write(instance.join("extra/my-mod.jar"), b"jar").unwrap();
+
+        let mods = apply(r#"
+            [[add]]
+            name = "Xaero's Minimap"
+            modrinth = "1bokaNcj"
+
+            [[add]]
+            name = "Mouse Tweaks"
+            curseforge = 60089
+            version = 7
+
+            [[add]]
+            name = "my own mod"
+            file = "extra/my-mod.jar"
+        "#, &instance);
+        fs::remove_dir_all(&instance).ok();
+
+        assert_eq!(mods.len(), 6);
+        assert!(matches!(mods[3].version, ModVersion::Latest));
+        assert!(matches!(mods[4].version, ModVersion::CurseForgeVersionId(7)));
+        match &mods[5].identifier {
+            ModIdentifier::Url { url, filename, sha512 } => {
+                assert!(url.starts_with("file://") && url.ends_with("/extra/my-mod.jar"));
+                assert_eq!(filename, "my-mod.jar");
+                assert_eq!(Some(sha512), file_hash("sha512", b"jar").as_ref());
+            },
+            identifier => panic!("expected a file url, got {identifier:?}")
+        }
+        assert!(mods[3..].iter().all(|mod_| mod_.locked));
+    }
+
+    #[test]
+    fn added_mods_keep_their_content_type() {
+        let mods = apply("[[add]]\nname = \"shaders\"\nmodrinth = \"HVnmMxH1\"\ncontent = \"shaderpack\"", Path::new("/instance"));
+        assert_eq!(mods[3].content, ContentType::ShaderPack);
+        assert!(mods[..3].iter().all(|mod_| mod_.content == ContentType::Mod));
+    }
+
+    #[test]
+    fn rejects_mismatched_versions() {
+        let invalid = [
+            "[pins]\nSodium = 42",
+            "[pins]\nJEI = \"abc123\"",
+            "[[add]]\nname = \"both\"\nmodrinth = \"AANobbMI\"\ncurseforge = 1",
+            "[[add]]\nname = \"none\"",
+            "[[add]]\nname = \"missing\"\nfile = \"missing.jar\"",
+        ];
+        for overrides in invalid {
+            assert!(toml::from_str::<UserOverrides>(overrides).unwrap().apply(Path::new("/instance"), manifest_mods()).is_err(), "{overrides}");
+        }
+    }
+}
@@ -1,65 +1,361 @@
-use std::{path::PathBuf, env::{consts, var}};
+use std::{path::PathBuf, env::{consts, var}, collections::BTreeMap};
 
 use serde::{Deserialize, Serialize};
 
-use crate::state::State;
+use crate::error::TinkarosError;
 
 #[derive(Serialize, Deserialize, Default, Debug)]
 pub struct AppConfig {
     pub init: bool,
-    pub launcher: String,
-    pub path: String,
     pub check_tinkaros_update: bool,
     pub max_concurrent_downloads: i32,
-    pub bleeding_edge_updates: bool
+    pub bleeding_edge_updates: bool,
+    #[serde(default)]
+    pub manifest_poll_interval: Option<u64>, // minutes, unset or 0 disables polling
+    #[serde(default)]
+    pub download_retries: Option<u32>, // extra tries after a network error, unset uses DEFAULT_DOWNLOAD_RETRIES
+    #[serde(default)]
+    pub packs: BTreeMap<String, PackConfig>,
+
+    // single pack configs from before packs existed, moved into packs.default on load
+    #[serde(default, skip_serializing)]
+    launcher: Option<String>,
+    #[serde(default, skip_serializing)]
+    path: Option<String>,
+    #[serde(default, skip_serializing)]
+    manifest_source: Option<String>
 }
 
 impl AppConfig {
-    pub fn new(init: bool, launcher: String, path: String, check_tinkaros_update: bool, max_concurrent_downloads: i32, bleeding_edge_updates: bool) -> Self { Self { init, launcher, path, check_tinkaros_update, max_concurrent_downloads, bleeding_edge_updates } }
+    pub fn new(init: bool, check_tinkaros_update: bool, max_concurrent_downloads: i32, bleeding_edge_updates: bool) -> Self { Self { init, check_tinkaros_update, max_concurrent_downloads, bleeding_edge_updates, ..Default::default() } }
+
+    pub fn migrate(mut self) -> Self {
+        if let (Some(launcher), Some(path)) = (self.launcher.take(), self.path.take()) {
+            self.packs.entry(DEFAULT_PACK.to_string()).or_insert(PackConfig::new(launcher, path, self.manifest_source.take()));
+        }
+        self
+    }
+
+    pub fn download_retries(&self) -> u32 {
+        self.download_retries.unwrap_or(DEFAULT_DOWNLOAD_RETRIES)
+    }
+}
+
+pub const DEFAULT_PACK: &str = "default";
+pub const DEFAULT_DOWNLOAD_RETRIES: u32 = 4;
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone)]
+pub struct PackConfig {
+    pub launcher: String,
+    pub path: String,
+    #[serde(default)]
+    pub manifest_source: Option<String>,
+    #[serde(default)]
+    pub public_key: Option<String>, // base64 ed25519 key, overrides the built in one
+    #[serde(default)]
+    pub allow_unsigned: bool, // development only, skips manifest signature checks
+    #[serde(default)]
+    pub optional_mods: BTreeMap<String, bool>, // mod name -> enabled, only for mods the manifest marks optional
+    #[serde(default)]
+    pub channel: Option<String> // release channel, the manifest's top level when unset
 }
 
+impl PackConfig {
+    pub fn new(launcher: String, path: String, manifest_source: Option<String>) -> Self { Self { launcher, path, manifest_source, ..Default::default() } }
+
+    // mods this pack config should end up with
+    pub fn mods<'a>(&'a self, modpack: &'a Modpack) -> impl Iterator<Item = &'a Mod> {
+        let side = self.side();
+        modpack.mods.iter().filter(move |mod_| mod_.side.installs_on(side) && mod_.enabled(&self.optional_mods))
+    }
+
+    // the "server" launcher installs a dedicated server instead of a client instance
+    pub fn side(&self) -> Side {
+        match self.launcher.as_str() {
+            "server" => Side::Server,
+            _ => Side::Client
+        }
+    }
+}
+
+// bump when the manifest format changes in a way older builds can't read
+pub const SCHEMA_VERSION: u32 = 1;
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Modpack {
+    #[serde(default = "default_schema_version")]
+    pub schema_version: u32,
     pub name: String,
     pub version: String,
     pub mod_loader: String,
     pub mod_loader_version: String,
     pub game_version: String,
-    pub overrides_url: String,
-    pub changelog_url: String,
+    #[serde(default)]
+    pub overrides_url: Option<String>,
+    // folders inside the overrides zip whose contents are extracted, the whole zip when empty
+    #[serde(default)]
+    pub overrides_folders: Vec<String>,
+    // folders extracted instead of overrides_folders on a dedicated server, empty means the same ones
+    #[serde(default)]
+    pub server_overrides_folders: Vec<String>,
+    // loose files installed next to (or instead of) the overrides zip
+    #[serde(default)]
+    pub override_files: Vec<OverrideFile>,
+    #[serde(default)]
+    pub changelog_url: Option<String>,
+    #[serde(default)]
     pub launcher_configs: StateLauncherConfigs,
     pub mods: Vec<Mod>,
+    // other release channels next to the top level one, see Modpack::select_channel
+    #[serde(default)]
+    pub channels: BTreeMap<String, Channel>,
+    // datapacks need a mod that loads them globally, which decides the folder
+    #[serde(default = "default_datapack_dir")]
+    pub datapack_dir: String,
+    #[serde(default)]
+    pub min_tinkaros_version: Option<String>,
+    // sha256 of every non-mod file the manifest points at, keyed by url
+    #[serde(default)]
+    pub hashes: BTreeMap<String, String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+fn default_schema_version() -> u32 { 1 }
+
+// the top level of the manifest is the stable channel
+pub const DEFAULT_CHANNEL: &str = "stable";
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Channel {
+    pub version: String,
+    pub mods: Vec<Mod>,
+    #[serde(default)]
+    pub overrides_url: Option<String>,
+}
+
+impl Modpack {
+    // swaps in the version, mods and overrides of a channel, everything else is shared
+    pub fn select_channel(self, channel: &str) -> Result<Self, TinkarosError> {
+        if channel == DEFAULT_CHANNEL {
+            return Ok(self);
+        }
+
+        let selected = self.channels.get(channel).cloned().ok_or_else(|| TinkarosError::UnknownChannel(channel.to_string()))?;
+        Ok(Self { version: selected.version, mods: selected.mods, overrides_url: selected.overrides_url, ..self })
+    }
+}
+
+pub const DEFAULT_DATAPACK_DIR: &str = "datapacks";
+fn default_datapack_dir() -> String { DEFAULT_DATAPACK_DIR.to_string() }
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct OverrideFile {
+    pub path: String, // relative to the instance
+    pub url: String,
+    pub hash_format: String,
+    pub hash: String,
+    #[serde(default)]
+    pub preserve: bool, // only written when it doesn't exist yet
+    #[serde(default)]
+    pub side: Side
+}
+
+// the only part of a manifest read when the full manifest can't be parsed
+#[derive(Deserialize)]
+pub struct ModpackRequirements {
+    pub min_tinkaros_version: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Default)]
 pub struct StateLauncherConfigs {
-    pub prism_url: String,
-    pub curseforge_url: String,
+    pub prism_url: Option<String>,
+    pub curseforge_url: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Mod {
     pub name: String,
     pub identifier: ModIdentifier,
-    pub version: ModVersion
+    #[serde(default)]
+    pub version: ModVersion,
+    #[serde(default)]
+    pub side: Side,
+    #[serde(default)]
+    pub content: ContentType,
+    // optional mods are toggled per user, the choice is kept in the pack config
+    #[serde(default)]
+    pub optional: bool,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub default_enabled: bool,
+    // pinned by the user, kept even with bleeding edge updates
+    #[serde(skip)]
+    pub locked: bool
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+impl Mod {
+    pub fn new(name: String, identifier: ModIdentifier, version: ModVersion, side: Side) -> Self {
+        Self { name, identifier, version, side, content: ContentType::Mod, optional: false, description: None, default_enabled: false, locked: false }
+    }
+
+    pub fn enabled(&self, choices: &BTreeMap<String, bool>) -> bool {
+        !self.optional || choices.get(&self.name).copied().unwrap_or(self.default_enabled)
+    }
+}
+
+// what a manifest entry is, decides which folder of the instance it's installed to
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentType {
+    #[default]
+    Mod,
+    ResourcePack,
+    ShaderPack,
+    DataPack
+}
+
+pub const CONTENT_TYPES: [ContentType; 4] = [ContentType::Mod, ContentType::ResourcePack, ContentType::ShaderPack, ContentType::DataPack];
+
+impl ContentType {
+    pub fn dir<'a>(&self, modpack: &'a Modpack) -> &'a str {
+        match self {
+            ContentType::Mod => "mods",
+            ContentType::ResourcePack => "resourcepacks",
+            ContentType::ShaderPack => "shaderpacks",
+            ContentType::DataPack => &modpack.datapack_dir
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ContentType::Mod => "jar",
+            _ => "zip"
+        }
+    }
+
+    // content type of a file in an mrpack/packwiz pack, from the folder it's in
+    pub fn from_path(path: &str) -> Option<Self> {
+        match path.split('/').next()? {
+            "mods" => Some(ContentType::Mod),
+            "resourcepacks" => Some(ContentType::ResourcePack),
+            "shaderpacks" => Some(ContentType::ShaderPack),
+            DEFAULT_DATAPACK_DIR => Some(ContentType::DataPack),
+            _ => None
+        }
+    }
+}
+
+// where a mod or override is needed, packs that don't say are installed everywhere
+#[derive(Debug, Deserialize, Serialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Client,
+    Server,
+    #[default]
+    Both
+}
+
+impl Side {
+    pub fn installs_on(&self, target: Side) -> bool {
+        *self == Side::Both || *self == target
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Default, Clone)]
 pub enum ModVersion {
     ModrinthVersionHash(String),
-    CurseForgeVersionId(i32)
+    CurseForgeVersionId(i32),
+    #[default]
+    Pinned, // the identifier already points at one exact file
+    Latest // newest compatible file, only for mods the user adds
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum ModIdentifier {
     ModrinthProject(String),
     CurseForgeProject(i32),
+    Url { url: String, filename: String, sha512: String },
+    GithubRelease {
+        repo: String,
+        tag: String,
+        asset: String,
+        // of the asset in tag, release assets can be replaced after upload, required in signed manifests
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        sha512: Option<String>
+    },
+}
+
+impl ModIdentifier {
+    // file name in the content folder for sources that don't come with their own metadata
+    pub fn filename(&self) -> Option<&str> {
+        match self {
+            ModIdentifier::Url { filename, .. } => Some(filename),
+            ModIdentifier::GithubRelease { asset, .. } => Some(asset),
+            _ => None
+        }
+    }
+
+    // id the mod's source knows the project by
+    pub fn project_id(&self) -> Option<String> {
+        match self {
+            ModIdentifier::ModrinthProject(id) => Some(id.clone()),
+            ModIdentifier::CurseForgeProject(id) => Some(id.to_string()),
+            _ => None
+        }
+    }
+
+    // the project behind the mod whichever way the manifest points at it, urls into modrinth's cdn count as the modrinth project
+    pub fn project(&self) -> Option<ModIdentifier> {
+        match self {
+            ModIdentifier::ModrinthProject(_) | ModIdentifier::CurseForgeProject(_) => Some(self.clone()),
+            ModIdentifier::Url { url, .. } => modrinth_project_id(url).map(ModIdentifier::ModrinthProject),
+            _ => None
+        }
+    }
+}
+
+// https://cdn.modrinth.com/data/<project id>/versions/<version id>/<file>
+pub fn modrinth_project_id(url: &str) -> Option<String> {
+    let mut parts = url.strip_prefix("https://cdn.modrinth.com/data/")?.split('/');
+    let project_id = parts.next()?;
+    match parts.next() {
+        Some("versions") => Some(project_id.to_string()),
+        _ => None
+    }
 }
 
 #[derive(Deserialize)]
 pub struct GithubRelease {
     pub tag_name: String,
+    #[serde(default)]
+    pub prerelease: bool,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub assets: Vec<GithubAsset>,
+}
+
+#[derive(Deserialize)]
+pub struct GithubAsset {
+    pub name: String,
+    pub browser_download_url: String,
+    // "sha256:<hex>", only set on assets uploaded after github started computing them
+    #[serde(default)]
+    pub digest: Option<String>,
+}
+
+impl GithubRelease {
+    // newest first, as returned by the github api
+    pub async fn list(repo: &str) -> Result<Vec<Self>, TinkarosError> {
+        let res_str = reqwest::Client::new()
+            .get(format!("https://api.github.com/repos/{repo}/releases"))
+            .header("User-Agent", "Tinkaros")
+            .send()
+            .await?
+            .text()
+            .await?;
+        Ok(serde_json::from_str(&res_str)?)
+    }
 }
 
 #[derive(Serialize)]
@@ -102,9 +398,9 @@ impl LauncherPath { // bunch of path declarations
             _ => PathBuf::from("")
         }
     }
-    pub async fn curseforge_instance() -> PathBuf { 
+    pub async fn curseforge_instance(modpack_name: &str) -> PathBuf { 
         match consts::OS {
-            "windows" => PathBuf::from(var("USERPROFILE").unwrap()).join(format!("curseforge/minecraft/Instances/{}", State::get().await.unwrap().modpack.name)),
+            "windows" => PathBuf::from(var("USERPROFILE").unwrap()).join(format!("curseforge/minecraft/Instances/{}", modpack_name)),
             _ => PathBuf::from("")
         }
     }
@@ -119,19 +415,76 @@ impl LauncherPath { // bunch of path declarations
             _ => panic!("incompatible os")
         }
     }
-    pub async fn prism_instance() -> PathBuf {
+    pub async fn prism_instance(modpack_name: &str) -> PathBuf {
         match consts::OS {
-            "windows" => PathBuf::from(var("APPDATA").unwrap()).join(format!("PrismLauncher/instances/{}/.minecraft", State::get().await.unwrap().modpack.name)),
+            "windows" => PathBuf::from(var("APPDATA").unwrap()).join(format!("PrismLauncher/instances/{}/.minecraft", modpack_name)),
             "linux" => {
                 let def = PathBuf::from(var("HOME").unwrap()).join(".local/share/PrismLauncher/instances");
                 let flatpak = PathBuf::from(var("HOME").unwrap()).join(".var/app/org.prismlauncher.PrismLauncher/data/PrismLauncher/instances");
                 if def.exists() {
-                    def.join(format!("{}/.minecraft", State::get().await.unwrap().modpack.name))
+                    def.join(format!("{}/.minecraft", modpack_name))
                 } else {
-                    flatpak.join(format!("{}/.minecraft", State::get().await.unwrap().modpack.name))
+                    flatpak.join(format!("{}/.minecraft", modpack_name))
                 }
             }
             _ => panic!("incompatible os")
         }
     }
-}
\ No newline at end of file
+}
+
+// smallest manifest that parses, shared by the tests of everything that needs a pack
+#[cfg(test)]
+pub fn test_manifest(mods: serde_json::Value) -> serde_json::Value {
+    serde_json::json!({
+        "name": "test",
+        "version": "1.0.0",
+        "mod_loader": "fabric",
+        "mod_loader_version": "0.15.0",
+        "game_version": "1.20.1",
+        "mods": mods
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AppConfig, DEFAULT_PACK};
+
+    const OLD_CONFIG: &str = r#"
+        init = true
+        check_tinkaros_update = true
+        max_concurrent_downloads = 8
+        bleeding_edge_updates = false
+        launcher = "prism"
+        path = "/instances/pack"
+        manifest_source = "https://example.com/manifest.json"
+    "#;
+
+    #[test]
+    fn single_pack_config_moves_into_default_pack() {
+        let config = toml::from_str::<AppConfig>(OLD_CONFIG).unwrap().migrate();
+        let pack = &config.packs[DEFAULT_PACK];
+        assert_eq!(pack.launcher, "prism");
+        assert_eq!(pack.path, "/instances/pack");
+        assert_eq!(pack.manifest_source.as_deref(), Some("https://example.com/manifest.json"));
+
+        // the old keys aren't written back
+        let saved = toml::to_string(&config).unwrap();
+        assert!(!toml::from_str::<toml::Table>(&saved).unwrap().contains_key("launcher"));
+        assert_eq!(toml::from_str::<AppConfig>(&saved).unwrap().migrate().packs.len(), 1);
+    }
+
+    #[test]
+    fn existing_default_pack_is_kept() {
+        let config = format!("{OLD_CONFIG}\n[packs.default]\nlauncher = \"curseforge\"\npath = \"/other\"");
+        let config = toml::from_str::<AppConfig>(&config).unwrap().migrate();
+        assert_eq!(config.packs.len(), 1);
+        assert_eq!(config.packs[DEFAULT_PACK].launcher, "curseforge");
+    }
+
+    #[test]
+    fn pack_configs_are_left_alone() {
+        let config = "init = true\ncheck_tinkaros_update = true\nmax_concurrent_downloads = 8\nbleeding_edge_updates = false\n[packs.other]\nlauncher = \"prism\"\npath = \"/other\"";
+        let config = toml::from_str::<AppConfig>(config).unwrap().migrate();
+        assert_eq!(config.packs.keys().collect::<Vec<_>>(), ["other"]);
+    }
+}
@@ -0,0 +1,209 @@
+use std::{collections::BTreeMap, path::{Path, Component}};
+
+use crate::error::{TinkarosError, ManifestProblem};
+
+use super::structs::{Modpack, ModIdentifier, ModVersion, SCHEMA_VERSION, DEFAULT_CHANNEL};
+
+const MOD_LOADERS: [&str; 4] = ["fabric", "forge", "quilt", "neoforge"];
+
+impl Modpack {
+    // checks everything at once so pack authors get the full list instead of the first failure
+    pub fn validate(&self) -> Result<(), TinkarosError> {
+        let mut problems = Vec::new();
+        let mut problem = |mod_name: Option<&str>, problem: String| problems.push(ManifestProblem { mod_name: mod_name.map(String::from), problem });
+
+        if self.schema_version > SCHEMA_VERSION {
+            problem(None, format!("schema version {} is newer than the supported version {}", self.schema_version, SCHEMA_VERSION));
+        }
+
+        if !MOD_LOADERS.contains(&self.mod_loader.as_str()) {
+            problem(None, format!("unknown mod loader \"{}\" (expected one of {})", self.mod_loader, MOD_LOADERS.join(", ")));
+        }
+        if self.mod_loader_version.trim().is_empty() {
+            problem(None, "mod_loader_version is empty".to_string());
+        }
+        if !is_game_version(&self.game_version) {
+            problem(None, format!("game_version \"{}\" is not a minecraft release version (e.g. 1.20.1)", self.game_version));
+        }
+
+        for (field, url) in [
+            ("overrides_url", self.overrides_url.as_ref()),
+            ("changelog_url", self.changelog_url.as_ref()),
+            ("launcher_configs.prism_url", self.launcher_configs.prism_url.as_ref()),
+            ("launcher_configs.curseforge_url", self.launcher_configs.curseforge_url.as_ref()),
+        ] {
+            if url.map(|url| url.trim().is_empty()).unwrap_or(false) {
+                problem(None, format!("{field} is empty"));
+            }
+        }
+
+        if self.datapack_dir.is_empty() || !Path::new(&self.datapack_dir).components().all(|component| matches!(component, Component::Normal(_))) {
+            problem(None, format!("datapack_dir \"{}\" is not a folder inside the instance", self.datapack_dir));
+        }
+
+        for (name, channel) in &self.channels {
+            if name == DEFAULT_CHANNEL {
+                problem(None, format!("channel \"{name}\" is the top level of the manifest, it can't be redefined"));
+            }
+            if channel.overrides_url.as_ref().map(|url| url.trim().is_empty()).unwrap_or(false) {
+                problem(None, format!("channels.{name}.overrides_url is empty"));
+            }
+        }
+
+        let channels = std::iter::once((None, &self.mods)).chain(self.channels.iter().map(|(name, channel)| (Some(name), &channel.mods)));
+        for (channel, mods) in channels {
+            let mut seen: BTreeMap<&ModIdentifier, &str> = BTreeMap::new();
+            for mod_ in mods {
+                let name = match channel {
+                    Some(channel) => format!("{} ({channel} channel)", mod_.name),
+                    None => mod_.name.clone()
+                };
+
+                match (&mod_.identifier, &mod_.version) {
+                    (ModIdentifier::ModrinthProject(_), ModVersion::ModrinthVersionHash(_))
+                    | (ModIdentifier::CurseForgeProject(_), ModVersion::CurseForgeVersionId(_))
+                    | (ModIdentifier::Url { .. } | ModIdentifier::GithubRelease { .. }, ModVersion::Pinned) => {},
+                    (identifier, version) => problem(Some(&name), format!("{identifier:?} can't be paired with {version:?}")),
+                }
+
+                // these names end up as paths in the content folder
+                if let Some(filename) = mod_.identifier.filename() {
+                    if filename.is_empty() || filename.contains(&['/', '\\'][..]) || filename.starts_with('.') {
+                        problem(Some(&name), format!("\"{filename}\" is not a plain file name"));
+                    }
+                }
+                let sha512 = match &mod_.identifier {
+                    ModIdentifier::Url { sha512, .. } => Some(sha512),
+                    ModIdentifier::GithubRelease { sha512, .. } => sha512.as_ref(),
+                    _ => None
+                };
+                if let Some(sha512) = sha512 {
+                    if sha512.len() != 128 || !sha512.chars().all(|c| c.is_ascii_hexdigit()) {
+                        problem(Some(&name), "sha512 is not a sha512 hex digest".to_string());
+                    }
+                }
+
+                if let Some(first) = seen.insert(&mod_.identifier, &mod_.name) {
+                    problem(Some(&name), format!("duplicate of {first}"));
+                }
+            }
+        }
+
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(TinkarosError::InvalidManifest(problems))
+        }
+    }
+}
+
+impl Modpack {
+    // a signature only covers files the manifest pins by hash, so signed manifests can't leave any out
+    pub fn validate_signed(&self) -> Result<(), TinkarosError> {
+        let mods = self.mods.iter().chain(self.channels.values().flat_map(|channel| channel.mods.iter()));
+        let unhashed: Vec<&str> = mods
+            .filter(|mod_| matches!(mod_.identifier, ModIdentifier::GithubRelease { sha512: None, .. }))
+            .map(|mod_| mod_.name.as_str())
+            .collect();
+
+        match unhashed.is_empty() {
+            true => Ok(()),
+            false => Err(TinkarosError::UntrustedManifest(format!("github release mods without a sha512: {}", unhashed.join(", "))))
+        }
+    }
+}
+
+// release versions only, like 1.20 or 1.20.1
+fn is_game_version(version: &str) -> bool {
+    let parts: Vec<&str> = version.split('.').collect();
+    (2..=3).contains(&parts.len()) && parts.iter().all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_digit()))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use crate::{error::TinkarosError, resolve::structs::{Modpack, test_manifest as manifest}};
+
+    fn modpack(manifest: Value) -> Modpack {
+        serde_json::from_value(manifest).unwrap()
+    }
+
+    fn problems(manifest: Value) -> Vec<String> {
+        match modpack(manifest).validate() {
+            Ok(()) => Vec::new(),
+            Err(TinkarosError::InvalidManifest(problems)) => problems.iter().map(|problem| problem.to_string()).collect(),
+            Err(err) => panic!("unexpected error {err}")
+        }
+    }
+
+    fn url_mod(name: &str, filename: &str, sha512: &str) -> Value {
+        json!({ "name": name, "identifier": { "Url": { "url": format!("https://example.com/{filename}"), "filename": filename, "sha512": sha512 } } })
+    }
+
+    fn github_mod(name: &str, sha512: Option<&str>) -> Value {
+        json!({ "name": name, "identifier": { "GithubRelease": { "repo": "owner/repo", "tag": "v1.0.0", "asset": "mod-1.0.0.jar", "sha512": sha512 } } })
+    }
+
+    #[test]
+    fn valid_manifest_passes() {
+        let mods = json!([
+            { "name": "Sodium", "identifier": { "ModrinthProject": "AANobbMI" }, "version": { "ModrinthVersionHash": "abc" } },
+            { "name": "JEI", "identifier": { "CurseForgeProject": 238222 }, "version": { "CurseForgeVersionId": 4712866 } },
+            url_mod("jar", "mod.jar", &"a".repeat(128))
+        ]);
+        assert!(problems(manifest(mods)).is_empty());
+    }
+
+    #[test]
+    fn every_problem_is_reported() {
+        let mut manifest = manifest(json!([]));
+        manifest["mod_loader"] = json!("rift");
+        manifest["mod_loader_version"] = json!(" ");
+        manifest["game_version"] = json!("23w13a");
+        manifest["datapack_dir"] = json!("../datapacks");
+        assert_eq!(problems(manifest).len(), 4);
+    }
+
+    #[test]
+    fn mismatched_versions_are_rejected() {
+        let mods = json!([{ "name": "Sodium", "identifier": { "ModrinthProject": "AANobbMI" }, "version": { "CurseForgeVersionId": 1 } }]);
+        let problems = problems(manifest(mods));
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("Sodium: "));
+    }
+
+    #[test]
+    fn file_names_and_hashes_are_checked() {
+        let mods = json!([
+            url_mod("escape", "../mod.jar", &"a".repeat(128)),
+            url_mod("hidden", ".mod.jar", &"a".repeat(128)),
+            url_mod("short hash", "mod.jar", "abc"),
+            github_mod("not hex", Some(&"z".repeat(128)))
+        ]);
+        assert_eq!(problems(manifest(mods)).len(), 4);
+    }
+
+    #[test]
+    fn duplicates_are_only_checked_within_a_channel() {
+        let sodium = json!({ "name": "Sodium", "identifier": { "ModrinthProject": "AANobbMI" }, "version": { "ModrinthVersionHash": "abc" } });
+        let mut manifest = manifest(json!([sodium, sodium]));
+        manifest["channels"] = json!({ "beta": { "version": "1.1.0-beta", "mods": [sodium] } });
+        assert_eq!(problems(manifest), ["Sodium: duplicate of Sodium"]);
+    }
+
+    #[test]
+    fn stable_channel_cant_be_redefined() {
+        let mut manifest = manifest(json!([]));
+        manifest["channels"] = json!({ "stable": { "version": "1.0.0", "mods": [] } });
+        assert_eq!(problems(manifest).len(), 1);
+    }
+
+    #[test]
+    fn signed_manifests_need_github_hashes() {
+        assert!(modpack(manifest(json!([github_mod("hashed", Some(&"a".repeat(128)))]))).validate_signed().is_ok());
+
+        let mut manifest = manifest(json!([]));
+        manifest["channels"] = json!({ "beta": { "version": "1.1.0-beta", "mods": [github_mod("unhashed", None)] } });
+        assert!(matches!(modpack(manifest).validate_signed(), Err(TinkarosError::UntrustedManifest(message)) if message.contains("unhashed")));
+    }
+}
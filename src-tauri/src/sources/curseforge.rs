@@ -0,0 +1,134 @@
+use async_trait::async_trait;
+use futures_util::future::join_all;
+use furse::{Furse, structures::file_structs::{File, FileRelationType, HashAlgo}};
+
+use crate::{resolve::structs::{Mod, Modpack, ModIdentifier, ModVersion, ContentType}, error::TinkarosError, update::{new_curseforge, structs::CombinedProjects}};
+
+use super::{ModSource, ResolvedFile, FileHash};
+
+pub struct CurseForgeSource {
+    curseforge: Furse,
+}
+
+impl CurseForgeSource {
+    pub fn new() -> Self {
+        Self { curseforge: new_curseforge() }
+    }
+}
+
+impl Default for CurseForgeSource {
+    fn default() -> Self { Self::new() }
+}
+
+fn resolved_file(file: File) -> Result<ResolvedFile, TinkarosError> {
+    let related = |relation: FileRelationType| file.dependencies.iter().filter(|dependency| dependency.relation_type == relation).map(|dependency| dependency.mod_id.to_string()).collect();
+    let (requires, incompatible) = (related(FileRelationType::RequiredDependency), related(FileRelationType::Incompatible));
+    // sha1 when curseforge has it, every file has a fingerprint
+    let hash = match file.hashes.iter().find(|hash| matches!(hash.algo, HashAlgo::Sha1)) {
+        Some(sha1) => FileHash::new("sha1", &sha1.value),
+        None => FileHash::new("murmur2", &file.file_fingerprint.to_string())
+    };
+
+    match file.download_url {
+        Some(url) => Ok(ResolvedFile {
+            project: Some(file.mod_id.to_string()),
+            requires,
+            incompatible,
+            hash: Some(hash),
+            size: Some(file.file_length as u64),
+            pinned: Some((ModIdentifier::CurseForgeProject(file.mod_id), ModVersion::CurseForgeVersionId(file.id))),
+            ..ResolvedFile::new(file.file_name, url.to_string())
+        }),
+        None => Err(TinkarosError::Update(format!("{} can't be downloaded outside of the curseforge app", file.file_name)))
+    }
+}
+
+// curseforge lists loaders next to game versions, by their display names
+fn curseforge_loader(modpack: &Modpack) -> String {
+    match modpack.mod_loader.as_str() {
+        "fabric" => "Fabric".to_string(),
+        "forge" => "Forge".to_string(),
+        "quilt" => "Quilt".to_string(),
+        "neoforge" => "NeoForge".to_string(),
+        mod_loader => mod_loader.to_string()
+    }
+}
+
+impl CurseForgeSource {
+    // TinkarosError isn't Send, so this reports plain messages for use in joined tasks
+    async fn latest_file(&self, mod_id: i32, content: ContentType, mod_loader: &str, game_version: &str) -> Result<Option<File>, String> {
+        let files = self.curseforge.get_mod_files(mod_id).await.map_err(|_| "unable to fetch mod versions".to_string())?;
+        // only mods are tagged with a loader
+        Ok(files.into_iter()
+            .filter(|file| file.is_available && (content != ContentType::Mod || file.game_versions.iter().any(|version| version == mod_loader)) && file.game_versions.iter().any(|version| version == game_version))
+            .max_by_key(|file| file.file_date))
+    }
+}
+
+#[async_trait]
+impl ModSource for CurseForgeSource {
+    fn handles(&self, mod_: &Mod) -> bool {
+        matches!(mod_.identifier, ModIdentifier::CurseForgeProject(_))
+    }
+
+    fn project_identifier(&self, project: &str) -> Option<ModIdentifier> {
+        project.parse().ok().map(ModIdentifier::CurseForgeProject)
+    }
+
+    async fn resolve_pinned(&self, mods: &[&Mod], _modpack: &Modpack) -> Result<Vec<ResolvedFile>, TinkarosError> {
+        let file_ids = mods.iter().filter_map(|mod_| match &mod_.version {
+            ModVersion::CurseForgeVersionId(id) => Some(*id),
+            _ => None
+        }).collect();
+
+        let files = self.curseforge.get_files(file_ids).await.map_err(|_| TinkarosError::FetchModVersions)?;
+        files.into_iter().map(resolved_file).collect()
+    }
+
+    async fn resolve_latest(&self, mods: &[&Mod], modpack: &Modpack) -> Result<Vec<ResolvedFile>, TinkarosError> {
+        let mod_loader = curseforge_loader(modpack);
+        let content = mods.first().map(|mod_| mod_.content).unwrap_or_default();
+        let tasks = mods.iter().filter_map(|mod_| match &mod_.identifier {
+            ModIdentifier::CurseForgeProject(id) => Some((*id, mod_.name.as_str())),
+            _ => None
+        }).map(|(mod_id, name)| {
+            let mod_loader = &mod_loader;
+            async move {
+                self.latest_file(mod_id, content, mod_loader, &modpack.game_version).await?.ok_or_else(|| format!("no compatible version of {name}"))
+            }
+        });
+
+        join_all(tasks).await.into_iter().map(|file| file.map_err(TinkarosError::Update).and_then(resolved_file)).collect()
+    }
+
+    async fn resolve_projects(&self, projects: &[String], content: ContentType, modpack: &Modpack) -> Result<Vec<(String, Option<ResolvedFile>)>, TinkarosError> {
+        let mod_loader = curseforge_loader(modpack);
+        let tasks = projects.iter().map(|project| {
+            let mod_loader = &mod_loader;
+            async move {
+                let file = match project.parse() {
+                    Ok(mod_id) => self.latest_file(mod_id, content, mod_loader, &modpack.game_version).await?,
+                    Err(_) => None
+                };
+                Ok::<_, String>((project.clone(), file))
+            }
+        });
+
+        let mut resolved = Vec::new();
+        for result in join_all(tasks).await {
+            let (project, file) = result.map_err(TinkarosError::Update)?;
+            resolved.push((project, file.map(resolved_file).transpose()?));
+        }
+        Ok(resolved)
+    }
+
+    async fn projects(&self, mods: &[&Mod]) -> Result<Vec<CombinedProjects>, TinkarosError> {
+        let ids = mods.iter().filter_map(|mod_| match &mod_.identifier {
+            ModIdentifier::CurseForgeProject(id) => Some(*id),
+            _ => None
+        }).collect();
+
+        let projects = self.curseforge.get_mods(ids).await.map_err(|err| TinkarosError::Unknown(Box::new(err)))?;
+        Ok(projects.into_iter().map(CombinedProjects::CurseForgeMod).collect())
+    }
+}
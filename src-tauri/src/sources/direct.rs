@@ -0,0 +1,104 @@
+use async_trait::async_trait;
+
+use crate::{resolve::structs::{Mod, Modpack, ModIdentifier, ModVersion, GithubRelease, GithubAsset}, error::TinkarosError, update::structs::CombinedProjects};
+
+use super::{ModSource, ResolvedFile, FileHash};
+
+// mods that point straight at a file, either a plain url or a github release asset
+pub struct DirectSource;
+
+fn pinned_file(identifier: &ModIdentifier) -> Option<ResolvedFile> {
+    let file = match identifier {
+        ModIdentifier::Url { url, filename, sha512 } => Some(ResolvedFile { hash: Some(FileHash::new("sha512", sha512)), ..ResolvedFile::new(filename.to_owned(), url.to_owned()) }),
+        ModIdentifier::GithubRelease { repo, tag, asset, sha512 } => Some(ResolvedFile {
+            hash: sha512.as_ref().map(|sha512| FileHash::new("sha512", sha512)),
+            ..ResolvedFile::new(asset.to_owned(), format!("https://github.com/{repo}/releases/download/{tag}/{asset}"))
+        }),
+        _ => None
+    };
+    file.map(|file| ResolvedFile { pinned: Some((identifier.clone(), ModVersion::Pinned)), ..file })
+}
+
+// the part of an asset name around the version, for asset names that carry the release version
+fn asset_pattern<'a>(tag: &str, asset: &'a str) -> Option<(&'a str, &'a str)> {
+    let version = tag.trim_start_matches('v');
+    if version.is_empty() { return None; }
+    asset.split_once(version)
+}
+
+// github's own digest, or a checksum published next to the asset as <asset>.sha256 / <asset>.sha512
+async fn github_asset_hash(release: &GithubRelease, asset: &GithubAsset) -> Result<FileHash, TinkarosError> {
+    if let Some(sha256) = asset.digest.as_deref().and_then(|digest| digest.strip_prefix("sha256:")) {
+        return Ok(FileHash::new("sha256", sha256));
+    }
+
+    for format in ["sha256", "sha512"] {
+        let checksum_name = format!("{}.{format}", asset.name);
+        let checksum = match release.assets.iter().find(|release_asset| release_asset.name == checksum_name) {
+            Some(checksum) => checksum,
+            None => continue
+        };
+        let res_str = reqwest::Client::new()
+            .get(&checksum.browser_download_url)
+            .header("User-Agent", "Tinkaros")
+            .send()
+            .await?
+            .text()
+            .await?;
+        // sha256sum style, the hash followed by the file name
+        if let Some(value) = res_str.split_whitespace().next() {
+            return Ok(FileHash::new(format, value));
+        }
+    }
+
+    Err(TinkarosError::Update(format!("{} in {} has no digest or published checksum to verify it against", asset.name, release.tag_name)))
+}
+
+// follows the newest stable release, the asset is the one named like the pinned one with the new version in place of the old
+async fn latest_github_asset(repo: &str, tag: &str, asset: &str) -> Result<ResolvedFile, TinkarosError> {
+    let releases = GithubRelease::list(repo).await?;
+    let latest = releases.into_iter()
+        .find(|release| !release.prerelease && !release.draft)
+        .ok_or_else(|| TinkarosError::Update(format!("{repo} has no stable releases")))?;
+    let matches = |name: &str| name == asset || match asset_pattern(tag, asset) {
+        Some((prefix, suffix)) => name.len() > prefix.len() + suffix.len() && name.starts_with(prefix) && name.ends_with(suffix),
+        None => false
+    };
+
+    let release_asset = latest.assets.iter()
+        .find(|release_asset| matches(&release_asset.name))
+        .filter(|release_asset| !release_asset.name.contains(&['/', '\\'][..]))
+        .ok_or_else(|| TinkarosError::Update(format!("{asset} not found in the latest release of {repo}")))?;
+    let hash = github_asset_hash(&latest, release_asset).await?;
+    Ok(ResolvedFile { hash: Some(hash), ..ResolvedFile::new(release_asset.name.clone(), release_asset.browser_download_url.clone()) })
+}
+
+#[async_trait]
+impl ModSource for DirectSource {
+    fn handles(&self, mod_: &Mod) -> bool {
+        matches!(mod_.identifier, ModIdentifier::Url { .. } | ModIdentifier::GithubRelease { .. })
+    }
+
+    async fn resolve_pinned(&self, mods: &[&Mod], _modpack: &Modpack) -> Result<Vec<ResolvedFile>, TinkarosError> {
+        Ok(mods.iter().filter_map(|mod_| pinned_file(&mod_.identifier)).collect())
+    }
+
+    async fn resolve_latest(&self, mods: &[&Mod], _modpack: &Modpack) -> Result<Vec<ResolvedFile>, TinkarosError> {
+        let mut files = Vec::new();
+        for mod_ in mods {
+            let file = match &mod_.identifier {
+                ModIdentifier::GithubRelease { repo, tag, asset, .. } => latest_github_asset(repo, tag, asset).await?,
+                identifier => match pinned_file(identifier) {
+                    Some(file) => file,
+                    None => continue
+                }
+            };
+            files.push(file);
+        }
+        Ok(files)
+    }
+
+    async fn projects(&self, _mods: &[&Mod]) -> Result<Vec<CombinedProjects>, TinkarosError> {
+        Ok(Vec::new())
+    }
+}
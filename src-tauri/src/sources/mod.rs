@@ -0,0 +1,371 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use async_trait::async_trait;
+
+use crate::{resolve::{structs::{Mod, Modpack, ModIdentifier, ModVersion, ContentType, CONTENT_TYPES}, overrides::USER_OVERRIDES_FILE}, error::TinkarosError, update::structs::CombinedProjects, state::signature::file_hash};
+
+pub mod modrinth;
+pub mod curseforge;
+pub mod direct;
+
+// a file a manifest entry resolved to, ready to be downloaded into its content folder
+#[derive(Debug, Clone)]
+pub struct ResolvedFile {
+    pub filename: String,
+    pub url: String,
+    pub content: ContentType,
+    // project ids in the namespace of the source that resolved the file
+    pub project: Option<String>,
+    pub requires: Vec<String>,
+    pub incompatible: Vec<String>,
+    // what the source says the file hashes to, github assets pinned without a sha512 come without one
+    pub hash: Option<FileHash>,
+    // bytes, when the source lists it
+    pub size: Option<u64>,
+    // manifest entry that pins exactly this file, exports reference files by it
+    pub pinned: Option<(ModIdentifier, ModVersion)>,
+}
+
+impl ResolvedFile {
+    pub fn new(filename: String, url: String) -> Self {
+        Self { filename, url, content: ContentType::Mod, project: None, requires: Vec::new(), incompatible: Vec::new(), hash: None, size: None, pinned: None }
+    }
+}
+
+// a hash in one of the formats file_hash knows
+#[derive(Debug, Clone)]
+pub struct FileHash {
+    pub format: String,
+    pub value: String,
+}
+
+impl FileHash {
+    pub fn new(format: &str, value: &str) -> Self {
+        Self { format: format.to_string(), value: value.to_string() }
+    }
+
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        file_hash(&self.format, bytes).map(|hash| hash.eq_ignore_ascii_case(&self.value)).unwrap_or(false)
+    }
+}
+
+pub struct Resolution {
+    pub files: Vec<ResolvedFile>,
+    pub warnings: Vec<String>,
+}
+
+// dependencies of dependencies are followed this many levels deep
+const MAX_DEPENDENCY_DEPTH: usize = 5;
+
+// somewhere mods can come from, every source gets the mods it handles in one batch of a single content type
+#[async_trait]
+pub trait ModSource: Send + Sync {
+    fn handles(&self, mod_: &Mod) -> bool;
+
+    // the manifest identifier of a project id this source resolved or listed as a dependency
+    fn project_identifier(&self, _project: &str) -> Option<ModIdentifier> {
+        None
+    }
+
+    // the exact files the manifest pins
+    async fn resolve_pinned(&self, mods: &[&Mod], modpack: &Modpack) -> Result<Vec<ResolvedFile>, TinkarosError>;
+
+    // the newest files compatible with the pack's game version and mod loader
+    async fn resolve_latest(&self, mods: &[&Mod], modpack: &Modpack) -> Result<Vec<ResolvedFile>, TinkarosError>;
+
+    // newest compatible file of each project, used for dependencies the manifest doesn't list
+    // None when a project has nothing for the pack's game version and loader, only mods are matched against the loader
+    async fn resolve_projects(&self, projects: &[String], _content: ContentType, _modpack: &Modpack) -> Result<Vec<(String, Option<ResolvedFile>)>, TinkarosError> {
+        Ok(projects.iter().map(|project| (project.clone(), None)).collect())
+    }
+
+    // project pages for the mod list, sources without any return nothing
+    async fn projects(&self, mods: &[&Mod]) -> Result<Vec<CombinedProjects>, TinkarosError>;
+}
+
+pub fn sources(app: &tauri::AppHandle) -> Result<Vec<Box<dyn ModSource>>, TinkarosError> {
+    Ok(vec![
+        Box::new(modrinth::ModrinthSource::new(app)?),
+        Box::new(curseforge::CurseForgeSource::new()),
+        Box::new(direct::DirectSource),
+    ])
+}
+
+// splits the mods between the sources and pulls in required dependencies the manifest is missing, mods no source handles are an error
+// excluded and disabled optional mods are never pulled in as a dependency, whatever needs them gets a warning instead
+pub async fn resolve(sources: &[Box<dyn ModSource>], mods: &[&Mod], excluded: &[&Mod], modpack: &Modpack, latest: bool) -> Result<Resolution, TinkarosError> {
+    if let Some(mod_) = mods.iter().find(|mod_| !sources.iter().any(|source| source.handles(mod_))) {
+        return Err(TinkarosError::Update(format!("no source can install {}", mod_.name)));
+    }
+
+    // (index of the source, file), dependency ids only mean something to the source that listed them
+    let mut files: Vec<(usize, ResolvedFile)> = Vec::new();
+    let mut warnings = Vec::new();
+    for content in CONTENT_TYPES {
+        for (i, source) in sources.iter().enumerate() {
+            let handled: Vec<&Mod> = mods.iter().copied().filter(|mod_| mod_.content == content && source.handles(mod_)).collect();
+            if handled.is_empty() { continue; }
+
+            // versions the user locked stay put even with bleeding edge updates
+            let (newest, pinned): (Vec<&Mod>, Vec<&Mod>) = handled.iter().partition(|mod_| (latest && !mod_.locked) || matches!(mod_.version, ModVersion::Latest));
+            let (unversioned, newest): (Vec<&Mod>, Vec<&Mod>) = newest.into_iter().partition(|mod_| matches!(mod_.version, ModVersion::Latest));
+
+            if !pinned.is_empty() {
+                let resolved = source.resolve_pinned(&pinned, modpack).await?;
+                files.extend(resolved.into_iter().map(|file| (i, ResolvedFile { content, ..file })));
+            }
+            if !newest.is_empty() {
+                let resolved = source.resolve_latest(&newest, modpack).await?;
+                files.extend(resolved.into_iter().map(|file| (i, ResolvedFile { content, ..file })));
+            }
+            if !unversioned.is_empty() {
+                let projects: Vec<String> = unversioned.iter().filter_map(|mod_| mod_.identifier.project_id()).collect();
+                for (project, file) in source.resolve_projects(&projects, content, modpack).await? {
+                    match file {
+                        Some(file) => files.push((i, ResolvedFile { content, ..file })),
+                        None if content == ContentType::Mod => warnings.push(format!("{project} has no version for {} {}", modpack.game_version, modpack.mod_loader)),
+                        None => warnings.push(format!("{project} has no version for {}", modpack.game_version))
+                    }
+                }
+            }
+        }
+    }
+
+    let mut requested: BTreeSet<(usize, String)> = BTreeSet::new();
+    for _ in 0..MAX_DEPENDENCY_DEPTH {
+        let mut added = false;
+        for (i, source) in sources.iter().enumerate() {
+            // projects already there, whichever source they came from
+            let installed: BTreeSet<ModIdentifier> = mods.iter().filter_map(|mod_| mod_.identifier.project())
+                .chain(files.iter().filter_map(|(source, file)| file.project.as_ref().and_then(|project| sources[*source].project_identifier(project))))
+                .collect();
+            // project id -> file that needs it
+            let mut missing: BTreeMap<String, String> = BTreeMap::new();
+            for (_, file) in files.iter().filter(|(source, _)| *source == i) {
+                for project in &file.requires {
+                    let identifier = source.project_identifier(project);
+                    let listed = files.iter().any(|(source, other)| *source == i && other.project.as_ref() == Some(project))
+                        || identifier.as_ref().map(|identifier| installed.contains(identifier)).unwrap_or(false);
+                    if listed || requested.contains(&(i, project.clone())) { continue; }
+
+                    let is_project = |mod_: &Mod| match &identifier {
+                        Some(identifier) => mod_.identifier.project().as_ref() == Some(identifier),
+                        None => source.handles(mod_) && mod_.identifier.project_id().as_ref() == Some(project)
+                    };
+                    match excluded.iter().find(|mod_| is_project(mod_)) {
+                        Some(mod_) => {
+                            match mod_.optional {
+                                true => warnings.push(format!("{} requires {}, which is a disabled optional mod", file.filename, mod_.name)),
+                                false => warnings.push(format!("{} requires {}, which is excluded in {USER_OVERRIDES_FILE}", file.filename, mod_.name))
+                            }
+                            requested.insert((i, project.clone()));
+                        },
+                        None => { missing.entry(project.clone()).or_insert_with(|| file.filename.clone()); }
+                    }
+                }
+            }
+            if missing.is_empty() { continue; }
+
+            let projects: Vec<String> = missing.keys().cloned().collect();
+            requested.extend(projects.iter().map(|project| (i, project.clone())));
+            for (project, file) in source.resolve_projects(&projects, ContentType::Mod, modpack).await? {
+                let required_by = missing.get(&project).cloned().unwrap_or_default();
+                match file {
+                    // the same jar can come from another source
+                    Some(file) if files.iter().any(|(_, other)| other.filename == file.filename) => {},
+                    Some(file) => { files.push((i, file)); added = true; },
+                    None => warnings.push(format!("{required_by} requires {project}, which has no version for {} {}", modpack.game_version, modpack.mod_loader))
+                }
+            }
+        }
+        if !added { break; }
+    }
+
+    for (i, file) in &files {
+        for project in &file.incompatible {
+            if let Some((_, other)) = files.iter().find(|(source, other)| source == i && other.project.as_ref() == Some(project)) {
+                warnings.push(format!("{} is incompatible with {}", file.filename, other.filename));
+            }
+        }
+    }
+
+    Ok(Resolution { files: files.into_iter().map(|(_, file)| file).collect(), warnings })
+}
+
+pub async fn projects(sources: &[Box<dyn ModSource>], mods: &[&Mod]) -> Result<Vec<CombinedProjects>, TinkarosError> {
+    let mut projects = Vec::new();
+    for source in sources {
+        let handled: Vec<&Mod> = mods.iter().copied().filter(|mod_| source.handles(mod_)).collect();
+        if handled.is_empty() { continue; }
+
+        let found = source.projects(&handled).await?;
+        projects.extend(found);
+    }
+    Ok(projects)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet};
+
+    use async_trait::async_trait;
+
+    use crate::{error::TinkarosError, resolve::structs::{Mod, Modpack, ModIdentifier, ModVersion, ContentType, Side, test_manifest}, update::structs::CombinedProjects};
+
+    use super::{resolve, direct::DirectSource, ModSource, ResolvedFile};
+
+    // handles modrinth mods, file names say how each one was resolved
+    #[derive(Default)]
+    struct MockSource {
+        // project -> projects it requires
+        requires: BTreeMap<String, Vec<String>>,
+        // projects without a version for the pack
+        unavailable: BTreeSet<String>,
+    }
+
+    impl MockSource {
+        fn file(&self, project: &str, resolved_by: &str) -> ResolvedFile {
+            ResolvedFile {
+                project: Some(project.to_string()),
+                requires: self.requires.get(project).cloned().unwrap_or_default(),
+                ..ResolvedFile::new(format!("{project}-{resolved_by}.jar"), format!("https://example.com/{project}-{resolved_by}.jar"))
+            }
+        }
+    }
+
+    #[async_trait]
+    impl ModSource for MockSource {
+        fn handles(&self, mod_: &Mod) -> bool {
+            matches!(mod_.identifier, ModIdentifier::ModrinthProject(_))
+        }
+
+        fn project_identifier(&self, project: &str) -> Option<ModIdentifier> {
+            Some(ModIdentifier::ModrinthProject(project.to_string()))
+        }
+
+        async fn resolve_pinned(&self, mods: &[&Mod], _modpack: &Modpack) -> Result<Vec<ResolvedFile>, TinkarosError> {
+            Ok(mods.iter().filter_map(|mod_| mod_.identifier.project_id()).map(|project| self.file(&project, "pinned")).collect())
+        }
+
+        async fn resolve_latest(&self, mods: &[&Mod], _modpack: &Modpack) -> Result<Vec<ResolvedFile>, TinkarosError> {
+            Ok(mods.iter().filter_map(|mod_| mod_.identifier.project_id()).map(|project| self.file(&project, "latest")).collect())
+        }
+
+        async fn resolve_projects(&self, projects: &[String], _content: ContentType, _modpack: &Modpack) -> Result<Vec<(String, Option<ResolvedFile>)>, TinkarosError> {
+            Ok(projects.iter().map(|project| match self.unavailable.contains(project) {
+                true => (project.clone(), None),
+                false => (project.clone(), Some(self.file(project, "project")))
+            }).collect())
+        }
+
+        async fn projects(&self, _mods: &[&Mod]) -> Result<Vec<CombinedProjects>, TinkarosError> {
+            Ok(Vec::new())
+        }
+    }
+
+    fn modpack() -> Modpack {
+        serde_json::from_value(test_manifest(serde_json::json!([]))).unwrap()
+    }
+
+    fn modrinth_mod(project: &str, version: ModVersion) -> Mod {
+        Mod::new(project.to_string(), ModIdentifier::ModrinthProject(project.to_string()), version, Side::Both)
+    }
+
+    fn pinned(project: &str) -> Mod {
+        modrinth_mod(project, ModVersion::ModrinthVersionHash(format!("{project}-hash")))
+    }
+
+    fn requires(pairs: &[(&str, &[&str])]) -> BTreeMap<String, Vec<String>> {
+        pairs.iter().map(|(project, required)| (project.to_string(), required.iter().map(|required| required.to_string()).collect())).collect()
+    }
+
+    // sorted file names and the warnings
+    async fn resolve_files(source: MockSource, mods: &[Mod], excluded: &[Mod], latest: bool) -> (Vec<String>, Vec<String>) {
+        let sources: Vec<Box<dyn ModSource>> = vec![Box::new(source)];
+        let mods: Vec<&Mod> = mods.iter().collect();
+        let excluded: Vec<&Mod> = excluded.iter().collect();
+        let resolution = resolve(&sources, &mods, &excluded, &modpack(), latest).await.unwrap();
+
+        let mut files: Vec<String> = resolution.files.into_iter().map(|file| file.filename).collect();
+        files.sort();
+        (files, resolution.warnings)
+    }
+
+    fn locked_pack() -> Vec<Mod> {
+        vec![pinned("a"), modrinth_mod("b", ModVersion::Latest), Mod { locked: true, ..pinned("c") }]
+    }
+
+    #[tokio::test]
+    async fn pinned_mods_stay_pinned() {
+        let (files, warnings) = resolve_files(MockSource::default(), &locked_pack(), &[], false).await;
+        assert_eq!(files, ["a-pinned.jar", "b-project.jar", "c-pinned.jar"]);
+        assert!(warnings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn bleeding_edge_keeps_locked_mods() {
+        let (files, _) = resolve_files(MockSource::default(), &locked_pack(), &[], true).await;
+        assert_eq!(files, ["a-latest.jar", "b-project.jar", "c-pinned.jar"]);
+    }
+
+    #[tokio::test]
+    async fn missing_dependencies_are_pulled_in() {
+        let source = MockSource { requires: requires(&[("a", &["d"]), ("d", &["e"])]), ..Default::default() };
+        let (files, warnings) = resolve_files(source, &[pinned("a")], &[], false).await;
+        assert_eq!(files, ["a-pinned.jar", "d-project.jar", "e-project.jar"]);
+        assert!(warnings.is_empty());
+    }
+
+    #[tokio::test]
+    async fn listed_dependencies_are_not_resolved_again() {
+        let source = MockSource { requires: requires(&[("a", &["b"])]), ..Default::default() };
+        let (files, _) = resolve_files(source, &[pinned("a"), pinned("b")], &[], false).await;
+        assert_eq!(files, ["a-pinned.jar", "b-pinned.jar"]);
+    }
+
+    #[tokio::test]
+    async fn excluded_dependencies_are_not_pulled_in() {
+        let source = MockSource { requires: requires(&[("a", &["d"])]), ..Default::default() };
+        let (files, warnings) = resolve_files(source, &[pinned("a")], &[pinned("d")], false).await;
+        assert_eq!(files, ["a-pinned.jar"]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("excluded"));
+    }
+
+    #[tokio::test]
+    async fn disabled_optional_dependencies_are_not_pulled_in() {
+        let source = MockSource { requires: requires(&[("a", &["d"])]), ..Default::default() };
+        let (files, warnings) = resolve_files(source, &[pinned("a")], &[Mod { optional: true, ..pinned("d") }], false).await;
+        assert_eq!(files, ["a-pinned.jar"]);
+        assert_eq!(warnings, ["a-pinned.jar requires d, which is a disabled optional mod"]);
+    }
+
+    #[tokio::test]
+    async fn dependencies_listed_through_another_source_are_not_pulled_in() {
+        let sources: Vec<Box<dyn ModSource>> = vec![Box::new(MockSource { requires: requires(&[("a", &["d"])]), ..Default::default() }), Box::new(DirectSource)];
+        let url = "https://cdn.modrinth.com/data/d/versions/1/d-1.0.jar".to_string();
+        let direct = Mod::new("d".to_string(), ModIdentifier::Url { url, filename: "d-1.0.jar".to_string(), sha512: String::new() }, ModVersion::Pinned, Side::Both);
+        let mods = [pinned("a"), direct];
+        let mods: Vec<&Mod> = mods.iter().collect();
+
+        let resolution = resolve(&sources, &mods, &[], &modpack(), false).await.unwrap();
+        let mut files: Vec<String> = resolution.files.into_iter().map(|file| file.filename).collect();
+        files.sort();
+        assert_eq!(files, ["a-pinned.jar", "d-1.0.jar"]);
+    }
+
+    #[tokio::test]
+    async fn unavailable_dependencies_warn() {
+        let source = MockSource { requires: requires(&[("a", &["d"])]), unavailable: BTreeSet::from(["d".to_string()]) };
+        let (files, warnings) = resolve_files(source, &[pinned("a")], &[], false).await;
+        assert_eq!(files, ["a-pinned.jar"]);
+        assert_eq!(warnings, ["a-pinned.jar requires d, which has no version for 1.20.1 fabric"]);
+    }
+
+    #[tokio::test]
+    async fn mods_without_a_source_are_an_error() {
+        let sources: Vec<Box<dyn ModSource>> = vec![Box::new(MockSource::default())];
+        let mod_ = Mod::new("jar".to_string(), ModIdentifier::Url { url: "https://example.com/a.jar".to_string(), filename: "a.jar".to_string(), sha512: String::new() }, ModVersion::Pinned, Side::Both);
+        assert!(resolve(&sources, &[&mod_], &[], &modpack(), false).await.is_err());
+    }
+}
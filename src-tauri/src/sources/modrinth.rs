@@ -0,0 +1,95 @@
+use async_trait::async_trait;
+use ferinth::{Ferinth, structures::version::{LatestVersionBody, Version, DependencyType}};
+
+use crate::{resolve::structs::{Mod, Modpack, ModIdentifier, ModVersion, ContentType}, error::TinkarosError, update::{new_modrinth, structs::CombinedProjects}};
+
+use super::{ModSource, ResolvedFile, FileHash};
+
+pub struct ModrinthSource {
+    modrinth: Ferinth,
+}
+
+impl ModrinthSource {
+    pub fn new(app: &tauri::AppHandle) -> Result<Self, TinkarosError> {
+        Ok(Self { modrinth: new_modrinth(app).map_err(|err| TinkarosError::Unknown(Box::new(err)))? })
+    }
+}
+
+fn version_hashes(mods: &[&Mod]) -> Vec<String> {
+    mods.iter().filter_map(|mod_| match &mod_.version {
+        ModVersion::ModrinthVersionHash(hash) => Some(hash.to_owned()),
+        _ => None
+    }).collect()
+}
+
+fn primary_file(version: Version) -> Option<ResolvedFile> {
+    let related = |kind: DependencyType| version.dependencies.iter().filter(|dependency| dependency.dependency_type == kind).filter_map(|dependency| dependency.project_id.clone()).collect();
+    let (requires, incompatible) = (related(DependencyType::Required), related(DependencyType::Incompatible));
+
+    let file = match version.files.iter().position(|file| file.primary) {
+        Some(i) => version.files.into_iter().nth(i),
+        None => version.files.into_iter().next()
+    }?;
+    let (hash, size) = (Some(FileHash::new("sha512", &file.hashes.sha512)), Some(file.size as u64));
+    let pinned = Some((ModIdentifier::ModrinthProject(version.project_id.clone()), ModVersion::ModrinthVersionHash(file.hashes.sha1)));
+    Some(ResolvedFile { project: Some(version.project_id), requires, incompatible, hash, size, pinned, ..ResolvedFile::new(file.filename, file.url.to_string()) })
+}
+
+#[async_trait]
+impl ModSource for ModrinthSource {
+    fn handles(&self, mod_: &Mod) -> bool {
+        matches!(mod_.identifier, ModIdentifier::ModrinthProject(_))
+    }
+
+    fn project_identifier(&self, project: &str) -> Option<ModIdentifier> {
+        Some(ModIdentifier::ModrinthProject(project.to_string()))
+    }
+
+    async fn resolve_pinned(&self, mods: &[&Mod], _modpack: &Modpack) -> Result<Vec<ResolvedFile>, TinkarosError> {
+        let versions = self.modrinth.get_versions_from_hashes(version_hashes(mods)).await.map_err(|_| TinkarosError::FetchModVersions)?;
+        Ok(versions.into_values().filter_map(primary_file).collect())
+    }
+
+    async fn resolve_latest(&self, mods: &[&Mod], modpack: &Modpack) -> Result<Vec<ResolvedFile>, TinkarosError> {
+        // modrinth files everything that isn't a mod under its own loaders
+        let loaders = match mods.first().map(|mod_| mod_.content) {
+            Some(ContentType::ResourcePack) => vec!["minecraft".to_string()],
+            Some(ContentType::ShaderPack) => vec!["iris".to_string(), "optifine".to_string(), "canvas".to_string()],
+            Some(ContentType::DataPack) => vec!["datapack".to_string()],
+            _ => vec![modpack.mod_loader.clone()]
+        };
+        let filters = LatestVersionBody {
+            loaders,
+            game_versions: vec![modpack.game_version.clone()],
+        };
+        let versions = self.modrinth.latest_versions_from_hashes(version_hashes(mods), filters).await
+            .map_err(|_| TinkarosError::Update("unable to fetch latest versions".to_string()))?;
+        Ok(versions.into_values().filter_map(primary_file).collect())
+    }
+
+    async fn resolve_projects(&self, projects: &[String], content: ContentType, modpack: &Modpack) -> Result<Vec<(String, Option<ResolvedFile>)>, TinkarosError> {
+        let mod_loader = [modpack.mod_loader.as_str()];
+        // resource packs, shaders and datapacks don't depend on the mod loader
+        let loaders = match content {
+            ContentType::Mod => Some(&mod_loader[..]),
+            _ => None
+        };
+        let mut resolved = Vec::new();
+        for project in projects {
+            let versions = self.modrinth.list_versions_filtered(project, loaders, Some(&[modpack.game_version.as_str()]), None).await
+                .map_err(|_| TinkarosError::FetchModVersions)?;
+            resolved.push((project.clone(), versions.into_iter().next().and_then(primary_file)));
+        }
+        Ok(resolved)
+    }
+
+    async fn projects(&self, mods: &[&Mod]) -> Result<Vec<CombinedProjects>, TinkarosError> {
+        let ids: Vec<&str> = mods.iter().filter_map(|mod_| match &mod_.identifier {
+            ModIdentifier::ModrinthProject(id) => Some(id.as_str()),
+            _ => None
+        }).collect();
+
+        let projects = self.modrinth.get_multiple_projects(&ids).await.map_err(|err| TinkarosError::Unknown(Box::new(err)))?;
+        Ok(projects.into_iter().map(CombinedProjects::ModrinthProject).collect())
+    }
+}
@@ -0,0 +1,53 @@
+use std::{fs, path::PathBuf, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::{Deserialize, Serialize};
+use tauri::{Config, api::path};
+
+use crate::error::TinkarosError;
+
+use super::State;
+
+// last good manifest, kept so tinkaros still works without a connection
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ManifestCache {
+    #[serde(skip)]
+    pub pack: String,
+    pub source: String,
+    pub etag: Option<String>,
+    pub fetched_at: u64,
+    #[serde(default)]
+    pub signed: bool,
+    pub state: State,
+}
+
+impl ManifestCache {
+    pub fn new(pack: String, source: String, etag: Option<String>, signed: bool, state: State) -> Self {
+        Self { pack, source, etag, fetched_at: now(), signed, state }
+    }
+
+    fn path(pack: &str) -> PathBuf {
+        path::app_config_dir(&Config::default()).unwrap().join(format!("tinkaros/manifest_cache/{pack}.json"))
+    }
+
+    // only returns a cache that was fetched from the same source
+    pub fn load(pack: &str, source: &str) -> Option<Self> {
+        let file = fs::read_to_string(Self::path(pack)).ok()?;
+        let cache: Self = serde_json::from_str(&file).ok()?;
+        if cache.source == source { Some(Self { pack: pack.to_string(), ..cache }) } else { None }
+    }
+
+    pub fn save(&self) -> Result<(), TinkarosError> {
+        let path = Self::path(&self.pack);
+        fs::create_dir_all(path.parent().unwrap())?;
+        fs::write(path, serde_json::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn touch(&mut self) {
+        self.fetched_at = now();
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+}
@@ -1,28 +1,218 @@
-use std::sync::Arc;
+use std::{sync::Arc, fs, time::Duration, collections::BTreeMap};
 
+use reqwest::{Url, StatusCode, header::{ETAG, IF_NONE_MATCH}};
 use serde::{Deserialize, Serialize};
-use tokio::sync::OnceCell;
+use tokio::{sync::RwLock, time::sleep};
 
-use crate::{resolve::structs::Modpack, error::TinkarosError};
+use crate::{formats::{PackFormat, mrpack, curseforge, packwiz}, resolve::{structs::{Modpack, ModpackRequirements, DEFAULT_CHANNEL}, config::{get_manifest_source, get_config, get_pack_config, validate_pack_id}}, error::TinkarosError, update::status::manifest_updated};
 
-static STATE: OnceCell<Arc<State>> = OnceCell::const_new();
+use self::{cache::ManifestCache, signature::{SignaturePolicy, sha256_hex}};
+
+pub mod cache;
+pub mod signature;
+
+// every pack loaded so far, keyed by pack id
+static LIBRARY: RwLock<BTreeMap<String, Arc<State>>> = RwLock::const_new(BTreeMap::new());
 
 #[derive(Debug, Deserialize, Serialize)]
 pub struct State {
     pub modpack: Modpack,
+    #[serde(skip)]
+    pub pack: String,
+    // set when the manifest couldn't be fetched and the cached one is used instead
+    #[serde(skip)]
+    pub offline: bool,
+    // set when the manifest signature was verified, every file url then needs a listed hash
+    #[serde(skip)]
+    pub signed: bool,
+    // release channel the modpack was narrowed to, the cache keeps the whole manifest
+    #[serde(skip)]
+    pub channel: String,
+    // channel the pack config asks for when the manifest no longer has it, the default channel is used then
+    #[serde(skip)]
+    pub missing_channel: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct StateRequirements {
+    modpack: ModpackRequirements,
 }
 
 impl State {
-    pub async fn get() -> Result<Arc<Self>, TinkarosError> {
-        STATE.get_or_try_init(|| async {
-            let default_url = "https://gist.githubusercontent.com/evergales/ec9d01d863083becd062b378ca01a3d8/raw/ahms.json";
+    pub fn new(modpack: Modpack) -> Self {
+        Self { modpack, pack: String::new(), offline: false, signed: false, channel: DEFAULT_CHANNEL.to_string(), missing_channel: None }
+    }
+
+    pub async fn get(pack: &str) -> Result<Arc<Self>, TinkarosError> {
+        if let Some(state) = LIBRARY.read().await.get(pack) {
+            return Ok(Arc::clone(state));
+        }
+
+        // loaded without the lock so a slow manifest doesn't hold up every other pack
+        let state = Arc::new(Self::load(pack).await?);
+        let mut library = LIBRARY.write().await;
+        // another task may have got here first, everyone keeps using its state
+        Ok(Arc::clone(library.entry(pack.to_string()).or_insert(state)))
+    }
+
+    // manifest of a pack that's still being added, it isn't put in the library
+    pub async fn preview(pack: &str, source: &str) -> Result<Self, TinkarosError> {
+        validate_pack_id(pack)?;
+        let state = Self::fetch(pack, source).await?;
+        state.modpack.validate()?;
+        Ok(state)
+    }
+
+    // forgets a removed pack, a new pack with the same id loads its own manifest
+    pub async fn unload(pack: &str) {
+        LIBRARY.write().await.remove(pack);
+    }
+
+    // fetches the manifest again and swaps it in, readers holding the old Arc keep using it
+    pub async fn reload(pack: &str, app: &tauri::AppHandle) -> Result<Arc<Self>, TinkarosError> {
+        let state = Arc::new(Self::load(pack).await?);
+        let old = LIBRARY.write().await.insert(pack.to_string(), Arc::clone(&state));
+
+        if let Some(old) = old {
+            if old.modpack.version != state.modpack.version {
+                manifest_updated(pack, &old.modpack.version, &state.modpack.version, app)?;
+            }
+        }
+
+        Ok(state)
+    }
+
+    // background refresh of every loaded pack, interval is re-read from the config so changing it doesn't need a restart
+    pub async fn poll(app: tauri::AppHandle) {
+        loop {
+            let minutes = get_config().ok().and_then(|config| config.manifest_poll_interval).unwrap_or(0);
+            if minutes == 0 {
+                sleep(Duration::from_secs(60)).await;
+                continue;
+            }
+
+            sleep(Duration::from_secs(minutes * 60)).await;
+            let packs: Vec<String> = LIBRARY.read().await.keys().cloned().collect();
+            for pack in packs {
+                Self::reload(&pack, &app).await.ok();
+            }
+        }
+    }
 
-            let modpack = reqwest::get(default_url).await?.json::<State>().await?.modpack;
+    async fn load(pack: &str) -> Result<Self, TinkarosError> {
+        validate_pack_id(pack)?;
+        let source = get_manifest_source(pack)?;
+        let state = Self::fetch(pack, &source).await?;
+        state.modpack.validate()?;
+        if state.signed {
+            state.modpack.validate_signed()?;
+        }
 
-            Ok(Arc::new(Self {
-                modpack
-            }))
-            
-        }).await.cloned()
+        let (channel, missing_channel) = match get_pack_config(pack).ok().and_then(|config| config.channel) {
+            Some(channel) if channel != DEFAULT_CHANNEL && !state.modpack.channels.contains_key(&channel) => (DEFAULT_CHANNEL.to_string(), Some(channel)),
+            Some(channel) => (channel, None),
+            None => (DEFAULT_CHANNEL.to_string(), None)
+        };
+        let modpack = state.modpack.select_channel(&channel)?;
+        Ok(Self { modpack, pack: pack.to_string(), channel, missing_channel, ..state })
     }
-}
\ No newline at end of file
+
+    async fn fetch(pack: &str, source: &str) -> Result<Self, TinkarosError> {
+        let url = packwiz::pack_file_url(Url::parse(source).map_err(|_| TinkarosError::ManifestSource(source.to_string()))?);
+        let source = url.as_str();
+        let policy = SignaturePolicy::for_pack(pack)?;
+
+        match url.scheme() {
+            "https" => Self::fetch_remote(pack, source, url.clone(), &policy).await,
+            "file" => {
+                let path = url.to_file_path().map_err(|_| TinkarosError::ManifestSource(source.to_string()))?;
+                let manifest = fs::read(&path)?;
+                let signature = fs::read_to_string(format!("{}.sig", path.to_string_lossy())).ok();
+                let signed = policy.verify(&manifest, signature.as_deref())?;
+                Ok(Self { signed, ..Self::parse(source, &url, &manifest).await? })
+            },
+            _ => Err(TinkarosError::ManifestSource(source.to_string()))
+        }
+    }
+
+    async fn fetch_remote(pack: &str, source: &str, url: Url, policy: &SignaturePolicy) -> Result<Self, TinkarosError> {
+        // a cache the policy doesn't accept (anymore) still gets replaced by a full fetch, it's only rejected where it would be used
+        let cache = ManifestCache::load(pack, source);
+        let trusted = cache.as_ref().map(|cache| policy.check_cached(cache.signed).is_ok()).unwrap_or(false);
+
+        let mut request = reqwest::Client::new().get(url.clone());
+        if let Some(etag) = cache.as_ref().filter(|_| trusted).and_then(|cache| cache.etag.as_ref()) {
+            request = request.header(IF_NONE_MATCH, etag);
+        }
+
+        let res = match request.send().await.and_then(|res| res.error_for_status()) {
+            Ok(res) => res,
+            Err(err) => return match cache {
+                Some(cache) => {
+                    policy.check_cached(cache.signed)?;
+                    Ok(Self { offline: true, signed: cache.signed, ..cache.state })
+                },
+                None => Err(err.into())
+            }
+        };
+
+        if let (StatusCode::NOT_MODIFIED, Some(mut cache)) = (res.status(), cache) {
+            policy.check_cached(cache.signed)?;
+            cache.touch();
+            cache.save().ok();
+            return Ok(Self { signed: cache.signed, ..cache.state });
+        }
+
+        let etag = res.headers().get(ETAG).and_then(|etag| etag.to_str().ok()).map(String::from);
+        let manifest = res.bytes().await?;
+        let signed = policy.verify(&manifest, Self::fetch_signature(source).await?.as_deref())?;
+
+        let state = Self::parse(source, &url, &manifest).await?;
+        state.modpack.validate()?; // never replace a good cache with a broken manifest
+        let cache = ManifestCache::new(pack.to_string(), source.to_string(), etag, signed, state);
+        cache.save().ok();
+
+        Ok(Self { signed, ..cache.state })
+    }
+
+    // a manifest using features this build doesn't know fails to parse,
+    // point at min_tinkaros_version instead of the serde error when the pack sets it
+    async fn parse(source: &str, url: &Url, manifest: &[u8]) -> Result<Self, TinkarosError> {
+        match PackFormat::from_source(url) {
+            PackFormat::Mrpack => return Ok(Self::new(mrpack::import(source, manifest)?)),
+            PackFormat::CurseForge => return Ok(Self::new(curseforge::import(source, manifest).await?)),
+            PackFormat::Packwiz => return Ok(Self::new(packwiz::import(url, manifest).await?)),
+            PackFormat::Tinkaros => {}
+        }
+
+        // only blame the version when this build is actually older, otherwise the manifest is just broken
+        serde_json::from_slice(manifest).map_err(|err| {
+            match serde_json::from_slice::<StateRequirements>(manifest) {
+                Ok(StateRequirements { modpack: ModpackRequirements { min_tinkaros_version: Some(required) } })
+                    if tauri::api::version::is_greater(env!("CARGO_PKG_VERSION"), required.trim_start_matches('v')).unwrap_or(false) =>
+                    TinkarosError::TinkarosOutdated { required: required.trim_start_matches('v').to_string(), latest: None },
+                _ => err.into()
+            }
+        })
+    }
+
+    // detached signature lives next to the manifest as <source>.sig
+    async fn fetch_signature(source: &str) -> Result<Option<String>, TinkarosError> {
+        let res = reqwest::get(format!("{source}.sig")).await?;
+        match res.status() {
+            StatusCode::NOT_FOUND => Ok(None),
+            _ => Ok(Some(res.error_for_status()?.text().await?))
+        }
+    }
+
+    // checks a downloaded file against the hash the manifest lists for its url
+    // unlisted files are only accepted when the manifest itself isn't signed
+    pub fn verify_file(&self, url: &str, bytes: &[u8]) -> Result<(), TinkarosError> {
+        match self.modpack.hashes.get(url) {
+            Some(expected) if expected.eq_ignore_ascii_case(&sha256_hex(bytes)) => Ok(()),
+            Some(_) => Err(TinkarosError::FileHashMismatch(url.to_string())),
+            None if self.signed => Err(TinkarosError::FileHashMismatch(url.to_string())),
+            None => Ok(())
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use base64::{Engine, engine::general_purpose::STANDARD};
+use ed25519_dalek::{Signature, VerifyingKey, Verifier};
+use sha1::Sha1;
+use sha2::{Sha256, Sha512, Digest};
+
+use crate::{error::TinkarosError, resolve::config::get_config};
+
+// set at build time, packs can trust a different key through their config, see the readme for the signing workflow
+const BUILTIN_PUBLIC_KEY: Option<&str> = option_env!("TINKAROS_MANIFEST_PUBLIC_KEY");
+
+pub struct SignaturePolicy {
+    pack: String,
+    public_key: Option<String>,
+    allow_unsigned: bool
+}
+
+impl SignaturePolicy {
+    pub fn for_pack(pack: &str) -> Result<Self, TinkarosError> {
+        let config = get_config()?.packs.remove(pack).unwrap_or_default();
+        Ok(Self {
+            pack: pack.to_string(),
+            // an unset ci secret still defines the variable, just empty
+            public_key: config.public_key.or_else(|| BUILTIN_PUBLIC_KEY.filter(|key| !key.is_empty()).map(String::from)),
+            allow_unsigned: config.allow_unsigned
+        })
+    }
+
+    // builds without a key can't trust anything, say how to get one in
+    fn missing_key(&self) -> TinkarosError {
+        TinkarosError::UntrustedManifest(format!(
+            "no trusted public key configured, build tinkaros with TINKAROS_MANIFEST_PUBLIC_KEY set or set public_key under [packs.{0}] in config.toml \
+            (allow_unsigned = true under [packs.{0}] skips the check, for pack development only)", self.pack
+        ))
+    }
+
+    // checks a detached base64 ed25519 signature over the raw manifest bytes
+    // returns whether the manifest was actually verified
+    pub fn verify(&self, manifest: &[u8], signature: Option<&str>) -> Result<bool, TinkarosError> {
+        if self.allow_unsigned { return Ok(false); }
+
+        let public_key = self.public_key.as_ref().ok_or_else(|| self.missing_key())?;
+        let signature = signature.ok_or_else(|| TinkarosError::UntrustedManifest("manifest is not signed, expected a base64 signature at <manifest source>.sig".to_string()))?;
+
+        let public_key: [u8; 32] = STANDARD.decode(public_key.trim()).ok()
+            .and_then(|key| key.try_into().ok())
+            .ok_or_else(|| TinkarosError::UntrustedManifest("trusted public key is not a base64 ed25519 key".to_string()))?;
+        let public_key = VerifyingKey::from_bytes(&public_key).map_err(|err| TinkarosError::UntrustedManifest(err.to_string()))?;
+
+        let signature = STANDARD.decode(signature.trim()).ok()
+            .and_then(|signature| Signature::from_slice(&signature).ok())
+            .ok_or_else(|| TinkarosError::UntrustedManifest("signature is malformed".to_string()))?;
+
+        public_key.verify(manifest, &signature).map_err(|_| TinkarosError::UntrustedManifest("signature does not match".to_string()))?;
+        Ok(true)
+    }
+
+    // a cached manifest can only be used if it was verified when it was fetched
+    pub fn check_cached(&self, signed: bool) -> Result<(), TinkarosError> {
+        match signed || self.allow_unsigned {
+            true => Ok(()),
+            false => Err(TinkarosError::UntrustedManifest("cached manifest was never verified".to_string()))
+        }
+    }
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    hex::encode(Sha256::digest(bytes))
+}
+
+// hex digest for the hash formats pack sources use, None when the format isn't supported
+pub fn file_hash(hash_format: &str, bytes: &[u8]) -> Option<String> {
+    match hash_format {
+        "sha1" => Some(hex::encode(Sha1::digest(bytes))),
+        "sha256" => Some(sha256_hex(bytes)),
+        "sha512" => Some(hex::encode(Sha512::digest(bytes))),
+        "murmur2" => Some(curseforge_fingerprint(bytes).to_string()),
+        _ => None
+    }
+}
+
+// curseforge's file fingerprint, 32 bit murmur2 with seed 1 over the file with whitespace bytes left out
+fn curseforge_fingerprint(bytes: &[u8]) -> u32 {
+    const M: u32 = 0x5bd1_e995;
+    let data: Vec<u8> = bytes.iter().copied().filter(|byte| !matches!(byte, 9 | 10 | 13 | 32)).collect();
+
+    let mut hash = 1 ^ data.len() as u32;
+    let mut chunks = data.chunks_exact(4);
+    for chunk in &mut chunks {
+        let mut k = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]).wrapping_mul(M);
+        k ^= k >> 24;
+        hash = hash.wrapping_mul(M) ^ k.wrapping_mul(M);
+    }
+
+    let rest = chunks.remainder();
+    if !rest.is_empty() {
+        for (i, byte) in rest.iter().enumerate() {
+            hash ^= u32::from(*byte) << (8 * i);
+        }
+        hash = hash.wrapping_mul(M);
+    }
+
+    hash ^= hash >> 13;
+    hash = hash.wrapping_mul(M);
+    hash ^ (hash >> 15)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{Engine, engine::general_purpose::STANDARD};
+    use ed25519_dalek::{Signer, SigningKey};
+
+    use crate::error::TinkarosError;
+
+    use super::{SignaturePolicy, curseforge_fingerprint, file_hash};
+
+    const MANIFEST: &[u8] = br#"{"name": "test"}"#;
+
+    fn key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32])
+    }
+
+    fn policy(public_key: Option<&SigningKey>, allow_unsigned: bool) -> SignaturePolicy {
+        SignaturePolicy {
+            pack: "test".to_string(),
+            public_key: public_key.map(|key| STANDARD.encode(key.verifying_key().as_bytes())),
+            allow_unsigned
+        }
+    }
+
+    fn sign(key: &SigningKey, manifest: &[u8]) -> String {
+        STANDARD.encode(key.sign(manifest).to_bytes())
+    }
+
+    fn untrusted(result: Result<bool, TinkarosError>) -> String {
+        match result {
+            Err(TinkarosError::UntrustedManifest(message)) => message,
+            other => panic!("expected an untrusted manifest, got {other:?}")
+        }
+    }
+
+    #[test]
+    fn valid_signature_verifies() {
+        let signature = format!("{}\n", sign(&key(1), MANIFEST));
+        assert!(policy(Some(&key(1)), false).verify(MANIFEST, Some(&signature)).unwrap());
+    }
+
+    #[test]
+    fn changed_manifest_is_rejected() {
+        let signature = sign(&key(1), MANIFEST);
+        assert_eq!(untrusted(policy(Some(&key(1)), false).verify(br#"{"name": "evil"}"#, Some(&signature))), "signature does not match");
+    }
+
+    #[test]
+    fn other_key_is_rejected() {
+        let signature = sign(&key(2), MANIFEST);
+        assert_eq!(untrusted(policy(Some(&key(1)), false).verify(MANIFEST, Some(&signature))), "signature does not match");
+    }
+
+    #[test]
+    fn unsigned_and_malformed_are_rejected() {
+        let policy = policy(Some(&key(1)), false);
+        assert!(untrusted(policy.verify(MANIFEST, None)).contains("not signed"));
+        assert_eq!(untrusted(policy.verify(MANIFEST, Some("not base64!"))), "signature is malformed");
+        assert_eq!(untrusted(policy.verify(MANIFEST, Some(&STANDARD.encode([0u8; 16])))), "signature is malformed");
+    }
+
+    #[test]
+    fn missing_key_says_how_to_add_one() {
+        let signature = sign(&key(1), MANIFEST);
+        let message = untrusted(policy(None, false).verify(MANIFEST, Some(&signature)));
+        assert!(message.contains("TINKAROS_MANIFEST_PUBLIC_KEY"));
+        assert!(message.contains("[packs.test]"));
+    }
+
+    #[test]
+    fn allow_unsigned_skips_the_check() {
+        assert!(!policy(None, true).verify(MANIFEST, None).unwrap());
+        assert!(policy(None, true).check_cached(false).is_ok());
+        assert!(policy(Some(&key(1)), false).check_cached(false).is_err());
+    }
+
+    // reference murmur2 (seed 1) values, the same algorithm curseforge fingerprints files with
+    #[test]
+    fn curseforge_fingerprint_matches_murmur2() {
+        assert_eq!(curseforge_fingerprint(b""), 1540447798);
+        assert_eq!(curseforge_fingerprint(b"abc"), 1621425345);
+        assert_eq!(curseforge_fingerprint(b"helloworld"), 2824650221);
+    }
+
+    #[test]
+    fn curseforge_fingerprint_skips_whitespace() {
+        assert_eq!(curseforge_fingerprint(b"hello world"), curseforge_fingerprint(b"helloworld"));
+        assert_eq!(curseforge_fingerprint(b"{\"mods\": []}\r\n\t"), 1129980694);
+        assert_eq!(file_hash("murmur2", b"tinkaros\n"), Some("3908086355".to_string()));
+    }
+}
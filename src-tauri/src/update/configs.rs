@@ -1,24 +1,42 @@
-use std::{path::PathBuf, fs};
+use std::{path::{Path, Component}, fs};
 
 use chrono::Utc;
 use fs_extra::dir::CopyOptions;
 use reqwest::Client;
-use serde_json::Map;
+use serde_json::{Map, json};
 
-use crate::{resolve::structs::LauncherPath, get_version, error::TinkarosError, state::State};
+use crate::{resolve::{structs::{LauncherPath, Modpack, Side}, config::get_config}, get_version, error::TinkarosError, state::State, formats::loader_version, sources::FileHash};
 
-use super::{status::{update_progress, update_status}, structs::{LauncherProfiles, Profile}, mods::download_file, zip_extract};
+use super::{status::{update_progress, update_status}, structs::{LauncherProfiles, Profile}, mods::download_verified, zip_extract_folders};
 
-pub async fn resolve_configs(app: &tauri::AppHandle, path: &PathBuf, launcher: String) -> Result<(), TinkarosError> {
+pub async fn resolve_configs(app: &tauri::AppHandle, data: &State, path: &Path, launcher: String, side: Side) -> Result<(), TinkarosError> {
     let client = Client::new();
-    let ver = get_version(path.to_string_lossy().to_string()).await?;
+    let retries = get_config()?.download_retries();
+    let ver = get_version(data.pack.clone()).await?;
 
-    if ver.version != ver.latest_version {
-        let overrides_url = State::get().await?.modpack.overrides_url.clone();
-        download_file(&client, &path.join("conf.zip"), &overrides_url).await?;
-        zip_extract(&path.join("conf.zip"), path).unwrap();
+    // switching channels re-extracts overrides even when both channels share a version string
+    if ver.version != ver.latest_version || ver.installed_channel != ver.channel {
+        if let Some(overrides_url) = &data.modpack.overrides_url {
+            let folders = match side {
+                Side::Server if !data.modpack.server_overrides_folders.is_empty() => &data.modpack.server_overrides_folders,
+                _ => &data.modpack.overrides_folders
+            };
+            // hashes in the manifest are sha256, unlisted overrides still go through verify_file for signed manifests
+            let hash = data.modpack.hashes.get(overrides_url).map(|hash| FileHash::new("sha256", hash));
+            if !download_verified(&client, &path.join("conf.zip"), overrides_url, hash.as_ref(), retries, None).await? {
+                return Err(TinkarosError::FileHashMismatch(overrides_url.clone()));
+            }
+            data.verify_file(overrides_url, &fs::read(path.join("conf.zip"))?)?;
+            zip_extract_folders(&path.join("conf.zip"), path, folders)?;
+        }
+        install_override_files(&client, data, path, side, retries).await?;
     } else { return Ok(()); }
 
+    // dedicated servers have no launcher to set up
+    if side == Side::Server {
+        return clean_up(path, app);
+    }
+
     if launcher == "default" {
         if LauncherPath::dotminecraft().await.exists() {
             let options = CopyOptions { overwrite: false, skip_exist: true, buffer_size: 64000, copy_inside: false, content_only: false, depth: 0 };
@@ -27,9 +45,9 @@ pub async fn resolve_configs(app: &tauri::AppHandle, path: &PathBuf, launcher: S
             update_progress(90, app)?;
         }
         if LauncherPath::dotminecraft().await.join("launcher_profiles.json").exists() {
-            let last_version_id = State::get().await.map_err(|err| TinkarosError::DataInvalid(err.to_string()))?.modpack.mod_loader_version.clone();
+            let last_version_id = data.modpack.mod_loader_version.clone();
             let launcher_profiles = fs::read_to_string(LauncherPath::dotminecraft().await.join("launcher_profiles.json"))?;
-            let modpack_name = State::get().await?.modpack.name.clone();
+            let modpack_name = data.modpack.name.clone();
 
             if !launcher_profiles.is_empty()  {
                 let mut launcher_json: LauncherProfiles = serde_json::from_str(&launcher_profiles).map_err(|_| TinkarosError::InvalidLauncherConfig)?;
@@ -52,7 +70,7 @@ pub async fn resolve_configs(app: &tauri::AppHandle, path: &PathBuf, launcher: S
                     let writer = fs::OpenOptions::new().read(true).write(true).truncate(true).open(LauncherPath::dotminecraft().await.join("launcher_profiles.json"))?;
                     serde_json::to_writer_pretty(writer, &launcher_json).map_err(|err| TinkarosError::Unknown(Box::new(err)))?;
                 } else {
-                    let latest_version = State::get().await.unwrap().modpack.mod_loader_version.clone();
+                    let latest_version = data.modpack.mod_loader_version.clone();
                     if launcher_json.profiles.get(&modpack_name).unwrap().last_version_id != latest_version {
                         launcher_json.profiles.get_mut(&modpack_name).unwrap().last_version_id = latest_version.to_owned();
                         if let Some(ver) = launcher_json.profiles.get_mut(&modpack_name) {
@@ -67,16 +85,23 @@ pub async fn resolve_configs(app: &tauri::AppHandle, path: &PathBuf, launcher: S
             update_progress(95, app)?;
         }
     } else if launcher == "curseforge" {
-        let url = &State::get().await?.modpack.launcher_configs.curseforge_url;
-        let text = reqwest::get(url).await?.text().await?;
-        fs::write(path.join("minecraftinstance.json"), text)?;
+        if let Some(url) = &data.modpack.launcher_configs.curseforge_url {
+            let bytes = reqwest::get(url).await?.bytes().await?;
+            data.verify_file(url, &bytes)?;
+            fs::write(path.join("minecraftinstance.json"), bytes)?;
+        }
         
     } else if launcher == "prism" {
-        let prism_main = &mut path.clone();
+        let prism_main = &mut path.to_path_buf();
         prism_main.pop();
-        let url = &State::get().await?.modpack.launcher_configs.prism_url;
-        let text = reqwest::get(url).await?.text().await?;
-        fs::write(prism_main.join("mmc-pack.json"), text)?;
+        match &data.modpack.launcher_configs.prism_url {
+            Some(url) => {
+                let bytes = reqwest::get(url).await?.bytes().await?;
+                data.verify_file(url, &bytes)?;
+                fs::write(prism_main.join("mmc-pack.json"), bytes)?;
+            },
+            None => fs::write(prism_main.join("mmc-pack.json"), mmc_pack(&data.modpack))?
+        }
 
 
         fs::write(
@@ -96,12 +121,50 @@ pub async fn resolve_configs(app: &tauri::AppHandle, path: &PathBuf, launcher: S
         iconKey=default
         name={}
         notes=
-        ", State::get().await?.modpack.name))?;
+        ", data.modpack.name))?;
     }
 
+    clean_up(path, app)
+}
+
+fn clean_up(path: &Path, app: &tauri::AppHandle) -> Result<(), TinkarosError> {
     update_status("cleaning up", app)?;
     fs::remove_dir_all(path.join("versions")).ok();
     fs::remove_file(path.join("conf.zip")).ok();
+    Ok(())
+}
 
+async fn install_override_files(client: &Client, data: &State, path: &Path, side: Side, retries: u32) -> Result<(), TinkarosError> {
+    for file in data.modpack.override_files.iter().filter(|file| file.side.installs_on(side)) {
+        let relative = Path::new(&file.path);
+        if !relative.components().all(|component| matches!(component, Component::Normal(_))) {
+            return Err(TinkarosError::DataInvalid(format!("override file {} is outside of the instance", file.path)));
+        }
+
+        let target = path.join(relative);
+        if file.preserve && target.exists() { continue; }
+
+        fs::create_dir_all(target.parent().unwrap())?;
+        if !download_verified(client, &target, &file.url, Some(&FileHash::new(&file.hash_format, &file.hash)), retries, None).await? {
+            return Err(TinkarosError::FileHashMismatch(file.path.clone()));
+        }
+    }
     Ok(())
+}
+
+// prism instance components for packs that don't ship their own mmc-pack.json
+fn mmc_pack(modpack: &Modpack) -> String {
+    let mut components = vec![json!({ "uid": "net.minecraft", "version": modpack.game_version, "important": true })];
+    let loader_uid = match modpack.mod_loader.as_str() {
+        "fabric" => Some("net.fabricmc.fabric-loader"),
+        "quilt" => Some("org.quiltmc.quilt-loader"),
+        "forge" => Some("net.minecraftforge"),
+        "neoforge" => Some("net.neoforged"),
+        _ => None
+    };
+    if let Some(uid) = loader_uid {
+        components.push(json!({ "uid": uid, "version": loader_version(&modpack.mod_loader, &modpack.mod_loader_version, &modpack.game_version) }));
+    }
+
+    json!({ "components": components, "formatVersion": 1 }).to_string()
 }
\ No newline at end of file
@@ -0,0 +1,109 @@
+use std::{fs, path::{Path, PathBuf}, collections::BTreeSet};
+
+use tauri::{Config, api::path};
+
+use crate::{error::TinkarosError, resolve::{config::get_config, structs::{CONTENT_TYPES, DEFAULT_DATAPACK_DIR}}, sources::FileHash, state::{State, signature::file_hash}};
+
+// formats good enough to name a file by, curseforge fingerprints collide too easily
+const CACHE_FORMATS: [&str; 3] = ["sha1", "sha256", "sha512"];
+
+// downloads shared by every instance and pack, stored as <format>/<hash>
+fn cache_dir() -> PathBuf {
+    path::app_cache_dir(&Config::default()).unwrap().join("tinkaros/downloads")
+}
+
+fn entry_path(hash: &FileHash) -> Option<PathBuf> {
+    // the hash ends up as a file name
+    if !CACHE_FORMATS.contains(&hash.format.as_str()) || hash.value.is_empty() || !hash.value.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(cache_dir().join(&hash.format).join(hash.value.to_ascii_lowercase()))
+}
+
+// puts the cached copy of a file at target, false when there's no intact one
+pub async fn restore(hash: &FileHash, target: &Path) -> bool {
+    let entry = match entry_path(hash) {
+        Some(entry) => entry,
+        None => return false
+    };
+
+    match tokio::fs::read(&entry).await {
+        Ok(bytes) if hash.matches(&bytes) => {},
+        Ok(_) => {
+            tokio::fs::remove_file(&entry).await.ok();
+            return false;
+        },
+        Err(_) => return false
+    }
+
+    tokio::fs::remove_file(target).await.ok();
+    tokio::fs::hard_link(&entry, target).await.is_ok() || tokio::fs::copy(&entry, target).await.is_ok()
+}
+
+// keeps a verified download for the next install, hardlinked when the cache is on the same disk as the instance
+pub async fn store(hash: &FileHash, path: &Path) {
+    let entry = match entry_path(hash) {
+        Some(entry) if !entry.exists() => entry,
+        _ => return
+    };
+
+    if tokio::fs::create_dir_all(entry.parent().unwrap()).await.is_err() { return; }
+    if tokio::fs::hard_link(path, &entry).await.is_err() {
+        tokio::fs::copy(path, &entry).await.ok();
+    }
+}
+
+// (format, path, size) of everything in the cache
+fn entries() -> Result<Vec<(&'static str, PathBuf, u64)>, TinkarosError> {
+    let mut entries = Vec::new();
+    for format in CACHE_FORMATS {
+        let dir = cache_dir().join(format);
+        if !dir.is_dir() { continue; }
+
+        for entry in dir.read_dir()?.filter_map(|entry| entry.ok()) {
+            let metadata = entry.metadata()?;
+            if metadata.is_file() {
+                entries.push((format, entry.path(), metadata.len()));
+            }
+        }
+    }
+    Ok(entries)
+}
+
+// bytes the cache takes up
+pub fn size() -> Result<u64, TinkarosError> {
+    Ok(entries()?.iter().map(|(_, _, size)| size).sum())
+}
+
+// removes entries no file in any instance's content folders matches, returns the bytes freed
+pub async fn prune() -> Result<u64, TinkarosError> {
+    let mut referenced: BTreeSet<(&str, String)> = BTreeSet::new();
+    let packs = get_config()?.packs;
+    for (pack, pack_config) in packs {
+        // without a manifest the datapack folder can only be guessed
+        let dirs: Vec<String> = match State::get(&pack).await {
+            Ok(state) => CONTENT_TYPES.iter().map(|content| content.dir(&state.modpack).to_string()).collect(),
+            Err(_) => vec!["mods".to_string(), "resourcepacks".to_string(), "shaderpacks".to_string(), DEFAULT_DATAPACK_DIR.to_string()]
+        };
+
+        for dir in dirs {
+            let dir = Path::new(&pack_config.path).join(dir);
+            if !dir.is_dir() { continue; }
+
+            for entry in dir.read_dir()?.filter_map(|entry| entry.ok()).filter(|entry| entry.path().is_file()) {
+                let bytes = fs::read(entry.path())?;
+                referenced.extend(CACHE_FORMATS.iter().filter_map(|format| Some((*format, file_hash(format, &bytes)?))));
+            }
+        }
+    }
+
+    let mut freed = 0;
+    for (format, path, size) in entries()? {
+        let hash = path.file_name().and_then(|name| name.to_str()).unwrap_or_default().to_string();
+        if !referenced.contains(&(format, hash)) {
+            fs::remove_file(&path)?;
+            freed += size;
+        }
+    }
+    Ok(freed)
+}
@@ -0,0 +1,67 @@
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{error::TinkarosError, resolve::structs::{Mod, Modpack, ModIdentifier, ModVersion, ContentType, Side}, sources::ResolvedFile};
+
+// lives in the instance, rewritten after every complete update
+pub const INSTALLED_FILE: &str = "tinkaros-installed.json";
+
+// what the last update put in the content folders
+// cleanup only removes files listed here and exports describe exactly these files
+#[derive(Debug, Deserialize, Serialize, Default)]
+pub struct InstalledFiles {
+    pub files: Vec<InstalledFile>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct InstalledFile {
+    // manifest name, dependencies only have their file name
+    pub name: String,
+    pub filename: String,
+    pub content: ContentType,
+    pub url: String,
+    pub side: Side,
+    #[serde(default)]
+    pub pinned: Option<(ModIdentifier, ModVersion)>,
+}
+
+impl InstalledFile {
+    pub fn new(file: &ResolvedFile, mod_: Option<&Mod>) -> Self {
+        Self {
+            name: mod_.map(|mod_| mod_.name.clone()).unwrap_or_else(|| file.filename.clone()),
+            filename: file.filename.clone(),
+            content: file.content,
+            url: file.url.clone(),
+            side: mod_.map(|mod_| mod_.side).unwrap_or_default(),
+            pinned: file.pinned.clone()
+        }
+    }
+
+    // relative to the instance
+    pub fn path(&self, modpack: &Modpack) -> String {
+        format!("{}/{}", self.content.dir(modpack), self.filename)
+    }
+}
+
+impl InstalledFiles {
+    // instances from before the record existed have nothing listed
+    pub fn load(instance: &Path) -> Self {
+        fs::read_to_string(instance.join(INSTALLED_FILE)).ok()
+            .and_then(|file| serde_json::from_str(&file).ok())
+            .unwrap_or_default()
+    }
+
+    // exports need to know what's installed, there's no guessing that for an instance that was never updated
+    pub fn load_existing(instance: &Path) -> Result<Self, TinkarosError> {
+        match instance.join(INSTALLED_FILE).exists() {
+            true => Ok(Self::load(instance)),
+            false => Err(TinkarosError::Update("nothing installed yet, update before exporting".to_string()))
+        }
+    }
+
+    pub fn save(&self, instance: &Path) -> Result<(), TinkarosError> {
+        fs::write(instance.join(INSTALLED_FILE), serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
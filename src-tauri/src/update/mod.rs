@@ -1,4 +1,4 @@
-use std::{fs::File, path::PathBuf};
+use std::{fs::{self, File}, path::{PathBuf, Path}, io};
 
 use ferinth::Ferinth;
 use furse::Furse;
@@ -8,6 +8,9 @@ pub mod structs;
 pub mod mods;
 pub mod status;
 pub mod configs;
+pub mod download_cache;
+pub mod progress;
+pub mod installed;
 
 pub fn new_modrinth(app: &tauri::AppHandle) -> Result<Ferinth, ferinth::Error> {
     return Ferinth::new("tinkaros", Some(app.package_info().version.to_string().as_str()), Some("@hbarni/Hbarni#4164"), None);
@@ -21,4 +24,28 @@ pub fn zip_extract(archive_file: &PathBuf, target_dir: &PathBuf) -> ZipResult<()
     let file = File::open(archive_file)?;
     let mut archive = zip::ZipArchive::new(file)?;
     archive.extract(target_dir)
+}
+
+// extracts the contents of the given folders into target_dir, later folders overwrite earlier ones
+pub fn zip_extract_folders(archive_file: &Path, target_dir: &Path, folders: &[String]) -> ZipResult<()> {
+    if folders.is_empty() {
+        return zip_extract(&archive_file.to_path_buf(), &target_dir.to_path_buf());
+    }
+
+    let file = File::open(archive_file)?;
+    let mut archive = zip::ZipArchive::new(file)?;
+    for folder in folders {
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let relative = match entry.enclosed_name().and_then(|name| name.strip_prefix(folder).ok().map(PathBuf::from)) {
+                Some(relative) if !entry.is_dir() && !relative.as_os_str().is_empty() => relative,
+                _ => continue
+            };
+
+            let out_path = target_dir.join(relative);
+            fs::create_dir_all(out_path.parent().unwrap())?;
+            io::copy(&mut entry, &mut File::create(out_path)?)?;
+        }
+    }
+    Ok(())
 }
\ No newline at end of file
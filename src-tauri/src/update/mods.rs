@@ -1,27 +1,41 @@
-use std::{path::Path, sync::{Arc, Mutex}};
+use std::{path::{Path, PathBuf}, sync::Arc, time::Duration, collections::hash_map::RandomState, hash::{BuildHasher, Hasher}};
 
-use chrono::{DateTime, Utc};
-use ferinth::structures::version::LatestVersionBody;
-use futures_util::{StreamExt, future::{join_all, join}};
-use reqwest::Client;
-use tokio::{sync::Semaphore, fs::File, io::AsyncWriteExt};
+use futures_util::{StreamExt, future::join_all};
+use reqwest::{Client, Url, StatusCode, header::RANGE};
+use tokio::{sync::Semaphore, fs::{File, OpenOptions}, io::AsyncWriteExt, time::sleep};
 
-use crate::{resolve::{structs::{ModVersion, ModIdentifier}, config::get_config}, error::TinkarosError, state::State};
+use crate::{resolve::{structs::{Mod, PackConfig, ContentType, CONTENT_TYPES}, config::get_config, overrides::UserOverrides}, error::TinkarosError, state::State, sources::{sources, resolve, ResolvedFile, Resolution, FileHash}};
 
-use super::{new_modrinth, new_curseforge, status::{update_progress, update_status}, structs::CombinedProjects};
+use super::{status::{update_progress, update_status}, structs::{UpdateReport, FailedDownload}, download_cache, progress::{DownloadTracker, FileProgress}, installed::{InstalledFiles, InstalledFile}};
 
-pub async fn update_mods(path: &Path, app: &tauri::AppHandle) -> Result<(), TinkarosError> {
-    let path = path.join("mods");
-    let data = State::get().await.map_err(|err| TinkarosError::DataInvalid(err.to_string()))?;
+// installs every mod the pack config wants and removes the rest, disabled optional mods included
+pub async fn update_mods(data: &State, pack_config: &PackConfig, app: &tauri::AppHandle) -> Result<UpdateReport, TinkarosError> {
+    let instance = Path::new(&pack_config.path);
     let config = get_config()?;
 
-    let (to_download, to_install) = match config.bleeding_edge_updates {
-      true => get_bleeding_updates(&data, &path, app).await?,
-      false => get_normal_updates(&data, &path, app).await? 
-    };
+    let sources = sources(app)?;
+    let overrides = UserOverrides::load(instance)?;
+    let excluded: Vec<Mod> = overrides.excluded(&data.modpack.mods).into_iter().cloned().collect();
+    // optional mods the player turned off stay off, even when something depends on them
+    let disabled = data.modpack.mods.iter().filter(|mod_| mod_.optional && !mod_.enabled(&pack_config.optional_mods));
+    let excluded: Vec<&Mod> = excluded.iter().chain(disabled).collect();
+    let mods = overrides.apply(instance, pack_config.mods(&data.modpack).cloned().collect())?;
+    let mods: Vec<&Mod> = mods.iter().collect();
+    if config.bleeding_edge_updates {
+        update_status("finding latest mod versions", app)?;
+    }
+    let Resolution { files: to_download, warnings } = resolve(&sources, &mods, &excluded, &data.modpack, config.bleeding_edge_updates).await?;
+    let to_install: Vec<ResolvedFile> = to_download.iter().filter(|file| !is_installed(file, &instance.join(file.content.dir(&data.modpack)))).cloned().collect();
+    for file in &to_install {
+        std::fs::create_dir_all(instance.join(file.content.dir(&data.modpack)))?;
+    }
+    for content in CONTENT_TYPES {
+        let resumable: Vec<&str> = to_install.iter().filter(|file| file.content == content).map(|file| file.filename.as_str()).collect();
+        remove_orphaned_parts(&instance.join(content.dir(&data.modpack)), &resumable)?;
+    }
 
-    let progress_per_mod = if !to_install.is_empty() {80.0 / to_install.len() as f32} else {0.0};
-    let progress = Arc::new(Mutex::new(5.0));
+    // downloads fill the progress bar from 5 to 85
+    let tracker = DownloadTracker::new(to_install.iter().filter_map(|file| file.size).sum(), (5.0, 85.0), app);
 
     let max_concurrent_downloads: usize = match config.max_concurrent_downloads {
         10..=85 => config.max_concurrent_downloads.try_into().unwrap(),
@@ -30,228 +44,229 @@ pub async fn update_mods(path: &Path, app: &tauri::AppHandle) -> Result<(), Tink
 
     let semaphore = Arc::new(Semaphore::new(max_concurrent_downloads));
     let client = Client::new();
+    let retries = config.download_retries();
 
     update_status("updating mods", app)?;
-    let tasks = to_install.into_iter().map(|(filename, url)| {
+    let tasks = to_install.into_iter().map(|file| {
         let semaphore = Arc::clone(&semaphore);
-        let progress = Arc::clone(&progress);
+        let tracker = Arc::clone(&tracker);
         let client = client.clone();
-        let app = app.clone();
-        let path = path.clone();
+        let path = instance.join(file.content.dir(&data.modpack));
+        let name = mod_name(&file, &mods);
 
         tokio::spawn(async move {
+            let ResolvedFile { filename, url, hash, size, .. } = file;
             let permit = semaphore.acquire().await.unwrap();
+            let progress = tracker.start(&filename, size);
+
+            let target = path.join(&filename);
+            let cached = match &hash {
+                Some(hash) => download_cache::restore(hash, &target).await,
+                None => false
+            };
+
+            let result = match cached {
+                true => Ok(()),
+                false => match download_verified(&client, &target, &url, hash.as_ref(), retries, Some(&progress)).await.map_err(|err| format!("unable to download from {url}: {err}")) {
+                    Ok(true) => Ok(()),
+                    Ok(false) => Err(TinkarosError::ModHashMismatch(name.clone()).to_string()),
+                    Err(reason) => Err(reason)
+                }
+            };
+            if let (Ok(()), Some(hash), false) = (&result, &hash, cached) {
+                download_cache::store(hash, &target).await;
+            }
 
-            download_file(&client, &path.join(&filename), &url).await.unwrap_or_else(|_| panic!("unable to download {filename} from {url}"));
-
-            *progress.lock().unwrap() += progress_per_mod;
-            update_progress(*progress.lock().unwrap() as i32, &app).unwrap();
-            
+            progress.finish();
             drop(permit);
+            result.map_err(|reason| FailedDownload { name, filename, reason })
         })
     });
 
-    let results = join_all(tasks).await;
-    for result in results {
-        if let Err(err) = result {
-            return Err(TinkarosError::Update(err.to_string()));
+    // every download gets to finish, failures are collected for the report
+    let mut failed = Vec::new();
+    for result in join_all(tasks).await {
+        if let Err(failure) = result.map_err(|err| TinkarosError::Update(err.to_string()))? {
+            failed.push(failure);
         }
     }
 
     update_progress(85, app)?;
 
-    // clean up old mods
-    path.read_dir()?
-    .filter_map(|entry| entry.ok())
-    .filter(|entry| {
-        let file_path = entry.path();
-        file_path.is_file()
-            && file_path.extension().map(|ext| ext == "jar").unwrap_or(false)
-            && !to_download.iter().any(|f| f.0 == file_path.file_name().unwrap().to_str().unwrap())
-    })
-    .for_each(|file| {
-        std::fs::remove_file(file.path()).expect("unable to remove file")
-    });
-    
-    Ok(())
-}
-
-fn file_exists(filename: &str, path: &Path) -> bool {
-    let file_path = path.join(filename);
-    file_path.exists()
-}
-
-
-pub async fn download_file(client: &Client, path: &Path, url: &str) -> Result<(), Box<dyn std::error::Error>> {
-    let response = client.get(url).send().await?;
-    let mut body = response.bytes_stream();
-
-    let mut file = File::create(path.join(path)).await?;
-
-    while let Some(chunk) = body.next().await {
-        let chunk = chunk?;
-        file.write_all(&chunk).await?;
+    // the old versions of mods that failed to download are all the instance has left, keep everything until a clean run
+    if !failed.is_empty() {
+        return Ok(UpdateReport { warnings, failed });
     }
 
-    Ok(())
-}
-
-pub async fn get_projects_from_ids(modrinth_ids: Vec<String>, curseforge_ids: Vec<i32>, app: &tauri::AppHandle) -> Result<Vec<CombinedProjects>, Box<dyn std::error::Error>> {
-    let modrinth = new_modrinth(app)?;
-    let curseforge = new_curseforge();
-
-    let modrinth_ids_slice = &modrinth_ids.iter().map(|s| s.as_str()).collect::<Vec<&str>>();
-
-    let modrinth_future = async {
-        if !modrinth_ids.is_empty() {
-            modrinth.get_multiple_projects(modrinth_ids_slice).await
-        } else {
-            Ok(Vec::new())
+    // clean up old files, resource packs, shaders and datapacks can be the player's own so only ones tinkaros installed go
+    let previous = InstalledFiles::load(instance);
+    for file in previous.files.iter().filter(|file| file.content != ContentType::Mod) {
+        let file_path = instance.join(file.path(&data.modpack));
+        if file_path.is_file() && !to_download.iter().any(|f| f.content == file.content && f.filename == file.filename) {
+            std::fs::remove_file(file_path)?;
         }
-    };
+    }
 
-    let curseforge_future = async {
-        if !curseforge_ids.is_empty() {
-            curseforge.get_mods(curseforge_ids).await
-        } else {
-            Ok(Vec::new())
+    let path = instance.join(ContentType::Mod.dir(&data.modpack));
+    if path.is_dir() {
+        let outdated = path.read_dir()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|file_path| {
+                let filename = file_path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+                file_path.is_file()
+                    && file_path.extension().map(|ext| ext == ContentType::Mod.extension()).unwrap_or(false)
+                    && !to_download.iter().any(|f| f.content == ContentType::Mod && f.filename == filename)
+            });
+        for file_path in outdated {
+            std::fs::remove_file(file_path)?;
         }
-    };
+    }
 
-    let (modrinth_projects, curseforge_mods) = join(modrinth_future, curseforge_future).await;
+    let installed = InstalledFiles { files: to_download.iter().map(|file| InstalledFile::new(file, resolved_mod(file, &mods))).collect() };
+    installed.save(instance)?;
 
-    let combined: Vec<CombinedProjects> = modrinth_projects?
-        .into_iter()
-        .map(CombinedProjects::ModrinthProject)
-        .chain(curseforge_mods?.into_iter().map(CombinedProjects::CurseForgeMod))
-        .collect();
-    
-    Ok(combined)
+    Ok(UpdateReport { warnings, failed })
 }
 
-async fn get_bleeding_updates(data: &State, mods_path: &Path, app: &tauri::AppHandle) -> Result<(Vec<(String, String)>, Vec<(String, String)>), TinkarosError> {
-    let modrinth = new_modrinth(app).unwrap();
-    let curseforge = new_curseforge();
-
-    update_status("finding latest mod versions", app)?;
-    
-    let mut modrinth_version_hashes = Vec::new();
-    let mut curseforge_ids = Vec::new();
-
-    for mod_ in &data.modpack.mods {
-        match &mod_.identifier {
-            ModIdentifier::ModrinthProject(_) => match &mod_.version {
-                ModVersion::ModrinthVersionHash(hash) => modrinth_version_hashes.push(hash.to_owned()),
-                ModVersion::CurseForgeVersionId(_) => return Err(TinkarosError::Update("found invalid mod list".to_string())),
-            },
-            ModIdentifier::CurseForgeProject(id) => curseforge_ids.push(id.to_owned())
-        }
+// a file only counts as installed when it still matches its hash, anything else is downloaded again
+fn is_installed(file: &ResolvedFile, path: &Path) -> bool {
+    let file_path = path.join(&file.filename);
+    match &file.hash {
+        Some(hash) => std::fs::read(file_path).map(|bytes| hash.matches(&bytes)).unwrap_or(false),
+        None => file_path.exists()
     }
+}
 
-    let latest_modrinth_versions: Vec<(String, String)> = modrinth.latest_versions_from_hashes(
-        modrinth_version_hashes,
-        LatestVersionBody {
-            loaders: vec![data.modpack.mod_loader.clone()],
-            game_versions: vec![data.modpack.game_version.clone()],
-        },
-    )
-    .await
-    .map_err(|_| TinkarosError::Update("unable to fetch latest versions".to_string()))?
-    .into_values()
-    .map(|version| {
-        let latest_version = match version.files.len() {
-            1 => version.files[0].to_owned(),
-            _ => version.files.into_iter().find(|file| file.primary).unwrap()
-        };
-
-        (latest_version.filename, latest_version.url.to_string())
-    })
-    .collect();
+// the mod a file was resolved for, dependencies don't have one
+fn resolved_mod<'a>(file: &ResolvedFile, mods: &[&'a Mod]) -> Option<&'a Mod> {
+    mods.iter().copied()
+        .find(|mod_| (file.project.is_some() && mod_.identifier.project_id() == file.project) || mod_.identifier.filename() == Some(file.filename.as_str()))
+}
 
-    let latest_curseforge_versions = Arc::new(Mutex::new(Vec::new()));
+// manifest name of the mod a file was resolved for, dependencies only have their file name
+fn mod_name(file: &ResolvedFile, mods: &[&Mod]) -> String {
+    resolved_mod(file, mods).map(|mod_| mod_.name.clone()).unwrap_or_else(|| file.filename.clone())
+}
 
-    let mut chars = data.modpack.mod_loader.chars();
-    let mod_loader_capitalized = match chars.next() {
-        None => String::new(),
-        Some(f) => f.to_uppercase().collect::<String>() + chars.as_str(),
-    };
+// a hash mismatch is usually a cut off transfer, so bad files are deleted and fetched again this many times
+const DOWNLOAD_ATTEMPTS: usize = 3;
 
-    let mut tasks = Vec::new();
+// unfinished downloads sit next to their target as <filename>.part until they're complete and verified
+const PART_EXTENSION: &str = "part";
 
-    for mod_id in curseforge_ids {
-        let latest_curseforge_versions = Arc::clone(&latest_curseforge_versions);
-        let curseforge = curseforge.clone();
-        let mod_loader_capitalized = mod_loader_capitalized.clone();
+fn part_path(path: &Path) -> PathBuf {
+    let mut part = path.as_os_str().to_owned();
+    part.push(format!(".{PART_EXTENSION}"));
+    PathBuf::from(part)
+}
 
-        let task = async move {
-            let mut compatible_files: Vec<(DateTime<Utc>, (String, String))> = Vec::new();
+// removes .part files of earlier runs that this update won't pick up again
+fn remove_orphaned_parts(dir: &Path, keep: &[&str]) -> Result<(), TinkarosError> {
+    if !dir.is_dir() { return Ok(()); }
 
-            curseforge.get_mod_files(mod_id).await.map_err(|_| TinkarosError::Update("unable to fetch mod versions".to_string())).unwrap()
-            .into_iter()
-            .for_each(|file| {
-                if file.is_available && file.game_versions.contains(&mod_loader_capitalized) && file.game_versions.contains(&data.modpack.game_version) {
-                    compatible_files.push((file.file_date, (file.file_name, file.download_url.unwrap().to_string())));
-                }
-            });
-    
-            let latest_compatible_file = compatible_files.into_iter().max_by_key(|(datetime, _)| *datetime).unwrap().1;
-    
-            latest_curseforge_versions.lock().unwrap().push((latest_compatible_file.0, latest_compatible_file.1));
-        };
+    for entry in dir.read_dir()?.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+        if !path.is_file() || path.extension().map(|ext| ext != PART_EXTENSION).unwrap_or(true) { continue; }
 
-        tasks.push(task);
-    };
+        let target = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+        if !keep.contains(&target) {
+            std::fs::remove_file(&path)?;
+        }
+    }
+    Ok(())
+}
 
-    join_all(tasks).await;
+// downloads a file and checks it against the hash its source lists, the target is only replaced once that passes
+// false when every attempt came out corrupted, nothing is left behind then
+pub async fn download_verified(client: &Client, path: &Path, url: &str, hash: Option<&FileHash>, retries: u32, progress: Option<&FileProgress>) -> Result<bool, Box<dyn std::error::Error>> {
+    let part = part_path(path);
+    // without a hash there's no telling whether a leftover part is from the same file
+    if hash.is_none() && part.exists() {
+        tokio::fs::remove_file(&part).await?;
+    }
 
-    let mut to_download: Vec<(String, String)> = Vec::new();
-    let mut to_install: Vec<(String, String)> = Vec::new();
+    for _ in 0..DOWNLOAD_ATTEMPTS {
+        download_retrying(client, &part, url, retries, progress).await?;
+        let verified = match hash {
+            Some(hash) => hash.matches(&tokio::fs::read(&part).await?),
+            None => true
+        };
 
-    let latest_curseforge_versions = latest_curseforge_versions.lock().unwrap().clone();
-    latest_modrinth_versions.into_iter().chain(latest_curseforge_versions).for_each(|(filename, url)| {
-        if !file_exists(filename.as_str(), mods_path) {
-            to_install.push((filename.clone(), url.clone()));
+        if verified {
+            tokio::fs::rename(&part, path).await?;
+            return Ok(true);
         }
-        to_download.push((filename, url));
-    });
-
-    Ok((to_download, to_install))
+        tokio::fs::remove_file(&part).await?;
+    }
+    Ok(false)
 }
 
-async fn get_normal_updates(data: &State, mods_path: &Path, app: &tauri::AppHandle) -> Result<(Vec<(String, String)>, Vec<(String, String)>), TinkarosError> {
-    let modrinth = new_modrinth(app).unwrap();
-    let curseforge = new_curseforge();
+// waits before retry n (from 0), doubling up to a cap with up to half of it randomized so parallel downloads don't retry in lockstep
+fn backoff(retry: u32) -> Duration {
+    let delay = (500u64 << retry.min(6)).min(30_000);
+    let jitter = RandomState::new().build_hasher().finish() % (delay / 2 + 1);
+    Duration::from_millis(delay - delay / 2 + jitter)
+}
 
-    let mut modrinth_version_hashes = Vec::new();
-    let mut curseforge_version_ids = Vec::new();
+// timeouts, dropped connections, 5xx and 429 answers and disk hiccups are worth another try, 4xx answers aren't
+fn is_transient(err: &(dyn std::error::Error + 'static)) -> bool {
+    match err.downcast_ref::<reqwest::Error>() {
+        Some(err) => match err.status() {
+            Some(status) => status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS,
+            None => err.is_timeout() || err.is_connect() || err.is_request() || err.is_body() || err.is_decode()
+        },
+        None => err.is::<std::io::Error>()
+    }
+}
 
-    for mod_ in &data.modpack.mods {
-        match &mod_.version {
-            ModVersion::ModrinthVersionHash(hash) => modrinth_version_hashes.push(hash.to_owned()),
-            ModVersion::CurseForgeVersionId(id) => curseforge_version_ids.push(id.to_owned()),
+// a retry continues the part where the failed attempt stopped
+async fn download_retrying(client: &Client, part: &Path, url: &str, retries: u32, progress: Option<&FileProgress>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut retry = 0;
+    loop {
+        match download_part(client, part, url, progress).await {
+            Ok(()) => return Ok(()),
+            Err(err) if retry < retries && is_transient(&*err) => {},
+            Err(err) => return Err(err)
         }
+        sleep(backoff(retry)).await;
+        retry += 1;
     }
+}
 
-    let mut to_download: Vec<(String, String)>  = Vec::new();
-    let mut to_install: Vec<(String, String)>  = Vec::new();
+// continues an existing part with a range request, servers that ignore the range send everything again
+async fn download_part(client: &Client, part: &Path, url: &str, progress: Option<&FileProgress>) -> Result<(), Box<dyn std::error::Error>> {
+    // local packs point their overrides at a file:// url
+    if let Some(local) = Url::parse(url).ok().filter(|url| url.scheme() == "file").and_then(|url| url.to_file_path().ok()) {
+        let copied = tokio::fs::copy(local, part).await?;
+        if let Some(progress) = progress { progress.set(copied); }
+        return Ok(());
+    }
 
-    for file in curseforge.get_files(curseforge_version_ids).await.expect("unable to fetch mod versions") {
-        if !file_exists(&file.file_name, mods_path) {
-            to_install.push((file.file_name.clone(), file.download_url.clone().unwrap().to_string()));
-        }
-        to_download.push((file.file_name, file.download_url.unwrap().to_string()));
+    let offset = tokio::fs::metadata(part).await.map(|metadata| metadata.len()).unwrap_or(0);
+    let mut response = match offset {
+        0 => client.get(url).send().await?,
+        _ => client.get(url).header(RANGE, format!("bytes={offset}-")).send().await?
     };
+    // the part already has every byte, or is longer than the file now is
+    if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        response = client.get(url).send().await?;
+    }
+    let response = response.error_for_status()?;
 
-    for version in modrinth.get_versions_from_hashes(modrinth_version_hashes).await.expect("unable to fetch mod versions") {
-        for file in version.1.files {
-            if file.primary {
-                if !file_exists(&file.filename, mods_path) {
-                    to_install.push((file.filename.clone(), file.url.clone().to_string()));
-                }
-                to_download.push((file.filename, file.url.to_string()));
-            }
-        }
+    let (mut file, mut written) = match response.status() {
+        StatusCode::PARTIAL_CONTENT => (OpenOptions::new().append(true).open(part).await?, offset),
+        _ => (File::create(part).await?, 0)
     };
 
-    Ok((to_download, to_install))
-}
\ No newline at end of file
+    let mut body = response.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk?;
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+        if let Some(progress) = progress { progress.set(written); }
+    }
+    file.flush().await?;
+
+    Ok(())
+}
@@ -0,0 +1,114 @@
+use std::{collections::BTreeMap, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use super::{status::{update_progress, download_progress}, structs::DownloadProgress};
+
+// events are spaced out so a few hundred parallel downloads don't flood the frontend
+const EMIT_INTERVAL: Duration = Duration::from_millis(200);
+
+// byte counts of every download in an update, turned into progress bar, speed and eta events
+pub struct DownloadTracker {
+    app: tauri::AppHandle,
+    // part of the progress bar the downloads fill, (start, end)
+    bar: (f64, f64),
+    state: Mutex<TrackerState>,
+}
+
+struct TrackerState {
+    // sizes the sources listed, files without one are added as they arrive
+    total: u64,
+    finished: u64,
+    // filename -> (bytes so far, listed size)
+    in_flight: BTreeMap<String, (u64, Option<u64>)>,
+    bytes_per_second: f64,
+    last_emit: Instant,
+    last_downloaded: u64,
+}
+
+impl TrackerState {
+    fn downloaded(&self) -> u64 {
+        self.finished + self.in_flight.values().map(|(bytes, _)| bytes).sum::<u64>()
+    }
+
+    fn total(&self) -> u64 {
+        self.total + self.in_flight.values().filter(|(_, size)| size.is_none()).map(|(bytes, _)| bytes).sum::<u64>()
+    }
+}
+
+impl DownloadTracker {
+    pub fn new(total: u64, bar: (f64, f64), app: &tauri::AppHandle) -> Arc<Self> {
+        let state = TrackerState { total, finished: 0, in_flight: BTreeMap::new(), bytes_per_second: 0.0, last_emit: Instant::now(), last_downloaded: 0 };
+        Arc::new(Self { app: app.clone(), bar, state: Mutex::new(state) })
+    }
+
+    pub fn start(self: &Arc<Self>, filename: &str, size: Option<u64>) -> FileProgress {
+        self.state.lock().unwrap().in_flight.insert(filename.to_string(), (0, size));
+        self.emit(true);
+        FileProgress { tracker: Arc::clone(self), filename: filename.to_string() }
+    }
+
+    fn emit(&self, force: bool) {
+        let mut state = self.state.lock().unwrap();
+        let elapsed = state.last_emit.elapsed();
+        if !force && elapsed < EMIT_INTERVAL { return; }
+
+        let downloaded = state.downloaded();
+        // smoothed so the speed doesn't jump around with every chunk, forced emits come too close together to measure
+        if elapsed >= EMIT_INTERVAL {
+            let rate = downloaded.saturating_sub(state.last_downloaded) as f64 / elapsed.as_secs_f64();
+            state.bytes_per_second = if state.bytes_per_second == 0.0 { rate } else { 0.7 * state.bytes_per_second + 0.3 * rate };
+            state.last_emit = Instant::now();
+            state.last_downloaded = downloaded;
+        }
+
+        let total = state.total();
+        let eta_seconds = match state.bytes_per_second > 0.0 {
+            true => Some((total.saturating_sub(downloaded) as f64 / state.bytes_per_second) as u64),
+            false => None
+        };
+        let payload = DownloadProgress {
+            total_bytes: total,
+            downloaded_bytes: downloaded,
+            bytes_per_second: state.bytes_per_second as u64,
+            eta_seconds,
+            in_flight: state.in_flight.keys().cloned().collect()
+        };
+        drop(state);
+
+        let (start, end) = self.bar;
+        let share = if total == 0 { 1.0 } else { (downloaded as f64 / total as f64).min(1.0) };
+        update_progress((start + (end - start) * share) as i32, &self.app).ok();
+        download_progress(payload, &self.app).ok();
+    }
+}
+
+// one file's share of a DownloadTracker
+pub struct FileProgress {
+    tracker: Arc<DownloadTracker>,
+    filename: String,
+}
+
+impl FileProgress {
+    // bytes of the file on disk so far, a restarted download goes back down
+    pub fn set(&self, bytes: u64) {
+        if let Some(file) = self.tracker.state.lock().unwrap().in_flight.get_mut(&self.filename) {
+            file.0 = bytes;
+        }
+        self.tracker.emit(false);
+    }
+
+    // done either way, failed files count as finished so the bar still reaches the end
+    pub fn finish(self) {
+        let mut state = self.tracker.state.lock().unwrap();
+        if let Some((bytes, size)) = state.in_flight.remove(&self.filename) {
+            match size {
+                Some(size) => state.finished += size,
+                None => {
+                    state.total += bytes;
+                    state.finished += bytes;
+                }
+            }
+        }
+        drop(state);
+        self.tracker.emit(true);
+    }
+}
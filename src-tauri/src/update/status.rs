@@ -2,7 +2,7 @@ use tauri::Manager;
 
 use crate::error::TinkarosError;
 
-use super::structs::{StatusUpdate, ProgressUpdate};
+use super::structs::{StatusUpdate, ProgressUpdate, DownloadProgress, ManifestUpdate};
 
 pub fn update_status(msg: &str, app: &tauri::AppHandle) -> Result<(), TinkarosError> {
     app.emit_all("status", StatusUpdate {status: msg.to_string()}).map_err(|_| TinkarosError::EmitEvent)?;
@@ -12,4 +12,14 @@ pub fn update_status(msg: &str, app: &tauri::AppHandle) -> Result<(), TinkarosEr
 pub fn update_progress(progress: i32, app: &tauri::AppHandle) -> Result<(), TinkarosError> {
     app.emit_all("progressUpdate", ProgressUpdate { progress }).map_err(|_| TinkarosError::EmitEvent)?;
     Ok(())
+}
+
+pub fn download_progress(progress: DownloadProgress, app: &tauri::AppHandle) -> Result<(), TinkarosError> {
+    app.emit_all("downloadProgress", progress).map_err(|_| TinkarosError::EmitEvent)?;
+    Ok(())
+}
+
+pub fn manifest_updated(pack: &str, old_version: &str, new_version: &str, app: &tauri::AppHandle) -> Result<(), TinkarosError> {
+    app.emit_all("manifestUpdated", ManifestUpdate { pack: pack.to_string(), old_version: old_version.to_string(), new_version: new_version.to_string() }).map_err(|_| TinkarosError::EmitEvent)?;
+    Ok(())
 }
\ No newline at end of file
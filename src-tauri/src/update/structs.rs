@@ -16,6 +16,22 @@ pub struct ProgressUpdate {
   pub progress: i32
 }
 
+#[derive(Clone, Serialize)]
+pub struct DownloadProgress {
+  pub total_bytes: u64,
+  pub downloaded_bytes: u64,
+  pub bytes_per_second: u64,
+  pub eta_seconds: Option<u64>,
+  pub in_flight: Vec<String>
+}
+
+#[derive(Clone, Serialize)]
+pub struct ManifestUpdate {
+  pub pack: String,
+  pub old_version: String,
+  pub new_version: String
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LauncherProfiles {
@@ -42,4 +58,19 @@ pub struct Profile {
 pub enum CombinedProjects {
   ModrinthProject(Project),
   CurseForgeMod(Mod),
-}
\ No newline at end of file
+}
+
+// handed back to the frontend once an update finishes
+#[derive(Debug, Serialize, Default)]
+pub struct UpdateReport {
+  pub warnings: Vec<String>,
+  // downloads that still failed after retrying, the instance isn't on the new version until these are gone
+  pub failed: Vec<FailedDownload>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct FailedDownload {
+  pub name: String,
+  pub filename: String,
+  pub reason: String
+}
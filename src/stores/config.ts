@@ -1,13 +1,21 @@
 import { writable } from "svelte/store";
 
+export type pack = {
+    launcher: String,
+    path: String,
+    manifest_source: String | undefined,
+    optional_mods: { [name: string]: boolean },
+    channel: string | undefined
+}
+
 export type config = {
     init: boolean,
-    launcher: String,
-    path: String
-    custom: boolean,
     check_tinkaros_update: boolean,
     max_concurrent_downloads: number,
-    bleeding_edge_updates: boolean
+    bleeding_edge_updates: boolean,
+    manifest_poll_interval: number | undefined,
+    download_retries: number | undefined,
+    packs: { [id: string]: pack }
 } | any
 
-export const config = writable<config>();
\ No newline at end of file
+export const config = writable<config>();
\ No newline at end of file
@@ -5,7 +5,9 @@ export type state = {
   updating: boolean,
   progress: number,
   updateState: string,
-  settingsShown: boolean
+  settingsShown: boolean,
+  pack: string,
+  addingPack: boolean
 } | any;
 
 export const state = writable<state>({
@@ -13,5 +15,7 @@ export const state = writable<state>({
   updating: false,
   progress: 0,
   updateState: "waiting..",
-  settingsShown: false
+  settingsShown: false,
+  pack: "default",
+  addingPack: false
 });